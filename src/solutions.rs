@@ -25,4 +25,8 @@ pub mod day22;
 pub mod day23_a;
 pub mod day23_b;
 pub mod day24;
-pub mod day25;
\ No newline at end of file
+pub mod day25;
+pub mod bignum;
+pub mod matrix;
+pub mod ocr;
+pub mod render;
@@ -0,0 +1,211 @@
+use std::cmp::Ordering;
+
+//Arbitrary-precision unsigned integers as little-endian base 2^32 limbs, with no
+//trailing zero limbs so that equal values always have equal representations
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BigUint
+{
+    limbs : Vec<u32>
+}
+
+impl BigUint
+{
+    pub fn zero() -> Self
+    {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn is_zero(&self) -> bool
+    {
+        self.limbs.is_empty()
+    }
+
+    //Builds a value from 4-bit groups, most significant first
+    pub fn from_nibbles(nibbles: &[u8]) -> Self
+    {
+        let mut limbs = vec![0u32; nibbles.len().div_ceil(8)];
+        for (i, &nibble) in nibbles.iter().rev().enumerate()
+        {
+            limbs[i / 8] |= ((nibble & 0xF) as u32) << (4 * (i % 8));
+        }
+        BigUint { limbs }.normalize()
+    }
+
+    //The 4-bit group i places up from the least significant, 0 past the top
+    pub fn nibble(&self, i: usize) -> u8
+    {
+        self.limbs.get(i / 8).map_or(0, |limb| (limb >> (4 * (i % 8))) as u8 & 0xF)
+    }
+
+    //Number of bits up to and including the highest set bit
+    pub fn bits(&self) -> usize
+    {
+        self.limbs.last().map_or(0, |top| 32 * self.limbs.len() - top.leading_zeros() as usize)
+    }
+
+    pub fn to_u64(&self) -> Option<u64>
+    {
+        self.to_u128().and_then(|value| u64::try_from(value).ok())
+    }
+
+    pub fn to_u128(&self) -> Option<u128>
+    {
+        match self.limbs.len() <= 4
+        {
+            true => Some(self.limbs.iter().rev().fold(0, |value, &limb| value << 32 | limb as u128)),
+            false => None
+        }
+    }
+
+    fn normalize(mut self) -> Self
+    {
+        while self.limbs.last() == Some(&0)
+        {
+            self.limbs.pop();
+        }
+        self
+    }
+
+    //Divides in place by a single limb, returning the remainder
+    fn div_rem_small(&mut self, divisor: u32) -> u32
+    {
+        let mut rem = 0u64;
+        for limb in self.limbs.iter_mut().rev()
+        {
+            let cur = rem << 32 | *limb as u64;
+            *limb = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        while self.limbs.last() == Some(&0)
+        {
+            self.limbs.pop();
+        }
+        rem as u32
+    }
+}
+
+impl From<u64> for BigUint
+{
+    fn from(value: u64) -> Self
+    {
+        BigUint { limbs: vec![value as u32, (value >> 32) as u32] }.normalize()
+    }
+}
+
+impl From<u128> for BigUint
+{
+    fn from(value: u128) -> Self
+    {
+        BigUint { limbs: (0..4).map(|i| (value >> (32 * i)) as u32).collect() }.normalize()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseBigUintError;
+
+//Decimal digits only
+impl std::str::FromStr for BigUint
+{
+    type Err = ParseBigUintError;
+
+    fn from_str(s: &str) -> Result<Self, ParseBigUintError>
+    {
+        if s.is_empty()
+        {
+            return Err(ParseBigUintError);
+        }
+        let ten = BigUint::from(10u64);
+        s.chars().try_fold(BigUint::zero(), |value, c|
+        {
+            let digit = c.to_digit(10).ok_or(ParseBigUintError)?;
+            Ok(&(&value * &ten) + &BigUint::from(digit as u64))
+        })
+    }
+}
+
+impl std::ops::Add<&BigUint> for &BigUint
+{
+    type Output = BigUint;
+
+    fn add(self, other: &BigUint) -> BigUint
+    {
+        let len = self.limbs.len().max(other.limbs.len());
+        let mut limbs = Vec::with_capacity(len + 1);
+        let mut carry = 0u64;
+        for i in 0..len
+        {
+            let sum = *self.limbs.get(i).unwrap_or(&0) as u64 + *other.limbs.get(i).unwrap_or(&0) as u64 + carry;
+            limbs.push(sum as u32);
+            carry = sum >> 32;
+        }
+        limbs.push(carry as u32);
+        BigUint { limbs }.normalize()
+    }
+}
+
+//Schoolbook multiplication; the values here are nowhere near big enough for anything cleverer to pay off
+impl std::ops::Mul<&BigUint> for &BigUint
+{
+    type Output = BigUint;
+
+    fn mul(self, other: &BigUint) -> BigUint
+    {
+        if self.is_zero() || other.is_zero()
+        {
+            return BigUint::zero();
+        }
+        let mut limbs = vec![0u32; self.limbs.len() + other.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate()
+        {
+            let mut carry = 0u64;
+            for (j, &b) in other.limbs.iter().enumerate()
+            {
+                let cur = limbs[i + j] as u64 + a as u64 * b as u64 + carry;
+                limbs[i + j] = cur as u32;
+                carry = cur >> 32;
+            }
+            limbs[i + other.limbs.len()] = carry as u32;
+        }
+        BigUint { limbs }.normalize()
+    }
+}
+
+impl Ord for BigUint
+{
+    fn cmp(&self, other: &Self) -> Ordering
+    {
+        self.limbs.len().cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering>
+    {
+        Some(self.cmp(other))
+    }
+}
+
+impl std::fmt::Display for BigUint
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        //Peel off nine decimal digits at a time, least significant first
+        let mut rest = self.clone();
+        let mut chunks = Vec::new();
+        while !rest.is_zero()
+        {
+            chunks.push(rest.div_rem_small(1_000_000_000));
+        }
+        match chunks.split_last()
+        {
+            None => write!(f, "0"),
+            Some((first, others)) =>
+            {
+                write!(f, "{}", first)?;
+                others.iter().rev().try_for_each(|chunk| write!(f, "{:09}", chunk))
+            }
+        }
+    }
+}
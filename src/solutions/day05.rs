@@ -1,20 +1,29 @@
-use std::{collections::HashMap, cmp::max, cmp::min};
+use std::{collections::{HashMap, HashSet}, cmp::max, cmp::min};
+use itertools::Itertools;
+use super::render::{Image, heat};
+
+const IMAGE_MAX_DIM : i64 = 1024;
+const REGION_SIZE : i64 = 10;
+const REGION_COUNT : usize = 10;
+
+type Point = (i64, i64);
 
 #[derive(Debug)]
 struct Line
 {
-    x1 : u32,
-    y1 : u32,
-    x2 : u32,
-    y2 : u32,
+    x1 : i64,
+    y1 : i64,
+    x2 : i64,
+    y2 : i64,
 }
 
 impl Line
 {
     fn new(input : &str) -> Self
     {
-        let values : Vec<_> = input.split(&[',', ' ', '-', '>'][..])
-            .filter_map(|s| s.parse::<u32>().ok())
+        let values : Vec<_> = input.split("->")
+            .flat_map(|s| s.split(','))
+            .map(|s| s.trim().parse::<i64>().unwrap())
             .collect();
 
         Self{x1: values[0], y1: values[1], x2: values[2], y2: values[3]}
@@ -22,45 +31,119 @@ impl Line
 
     fn is_horizontal(&self) -> bool
     {
-        self.x1 == self.x2
+        self.y1 == self.y2
     }
 
     fn is_vertical(&self) -> bool
     {
-        self.y1 == self.y2
+        self.x1 == self.x2
     }
 
-    fn intersect(&self, plane : &mut HashMap<(u32, u32), u32>)
+    fn intersect(&self, plane : &mut HashMap<Point, u32>)
     {
-        //Get points from start to end
-        //Cases: horizontal, vertical, diagonal(45 degrees exactly)
-        let d_x = max(self.x1, self.x2) - min(self.x1, self.x2);
-        let d_y = max(self.y1, self.y2) - min(self.y1, self.y2);
-        let num_steps = max(d_x, d_y);
-        let points : Vec<_> = (0..=num_steps).map(|n|
-        {
-            let x = Self::n_closer(self.x1, self.x2, n);
-            let y = Self::n_closer(self.y1, self.y2, n);
-            (x, y)
-        }).collect();
-
         //For each point, either insert 1 or increment
-        for &point in points.iter()
+        for point in self.points()
         {
             *plane.entry(point).or_insert(0) += 1;
         }
     }
 
-    fn n_closer(v1 : u32, v2 : u32, n : u32) -> u32
+    fn points(&self) -> impl Iterator<Item = Point>
     {
-        match v1 <= v2
+        //Rasterize onto the integer lattice: step by the primitive direction
+        //so every point lies exactly on the line, whatever the slope
+        let run = self.run();
+        (0..=run.steps).map(move |n| (run.origin.0 + n * run.dir.0, run.origin.1 + n * run.dir.1))
+    }
+
+    fn run(&self) -> Run
+    {
+        let d_x = self.x2 - self.x1;
+        let d_y = self.y2 - self.y1;
+        let steps = gcd(d_x.abs(), d_y.abs());
+        if steps == 0
+        {
+            return Run { origin: (self.x1, self.y1), dir: (1, 0), steps: 0 };
+        }
+
+        let dir = (d_x / steps, d_y / steps);
+        match dir.0 > 0 || (dir.0 == 0 && dir.1 > 0)
         {
-            true => min(v1 + n, v2),
-            false => max(v1 - n, v2)
+            true => Run { origin: (self.x1, self.y1), dir, steps },
+            false => Run { origin: (self.x2, self.y2), dir: (-dir.0, -dir.1), steps }
         }
     }
 }
 
+//A line segment as the lattice points origin + n*dir for n in 0..=steps,
+//with dir primitive and pointing right (or up, when vertical)
+#[derive(Debug, Clone, Copy)]
+struct Run
+{
+    origin : Point,
+    dir : Point,
+    steps : i64,
+}
+
+impl Run
+{
+    fn end(&self) -> Point
+    {
+        (self.origin.0 + self.steps * self.dir.0, self.origin.1 + self.steps * self.dir.1)
+    }
+
+    //Identifies the infinite line the run lies on
+    fn line_key(&self) -> (Point, i64)
+    {
+        (self.dir, cross(self.dir, self.origin))
+    }
+
+    fn crossing(&self, other : &Run) -> Option<Point>
+    {
+        let denom = cross(self.dir, other.dir) as i128;
+        if denom == 0
+        {
+            return None;
+        }
+
+        let offset = (other.origin.0 - self.origin.0, other.origin.1 - self.origin.1);
+        let a = cross(offset, other.dir) as i128;
+        let b = cross(offset, self.dir) as i128;
+        if a % denom != 0 || b % denom != 0
+        {
+            return None;
+        }
+
+        let (a, b) = (a / denom, b / denom);
+        match (0..=self.steps as i128).contains(&a) && (0..=other.steps as i128).contains(&b)
+        {
+            true => Some((self.origin.0 + a as i64 * self.dir.0, self.origin.1 + a as i64 * self.dir.1)),
+            false => None
+        }
+    }
+}
+
+//Position of a lattice point along a line with the given direction
+fn param(dir : Point, point : Point) -> i64
+{
+    let norm = dir.0 * dir.0 + dir.1 * dir.1;
+    (dir.0 * point.0 + dir.1 * point.1).div_euclid(norm)
+}
+
+fn cross(a : Point, b : Point) -> i64
+{
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn gcd(a : i64, b : i64) -> i64
+{
+    match b
+    {
+        0 => a,
+        _ => gcd(b, a % b)
+    }
+}
+
 pub fn solution_a(input: &str) -> String
 {
     let lines : Vec<_> = input.lines()
@@ -76,22 +159,238 @@ pub fn solution_b(input: &str) -> String
     let lines : Vec<_> = input.lines()
         .map(Line::new)
         .collect();
-    
+
     count_overlaps(lines)
 }
 
+pub fn solution_a_sweep(input: &str) -> String
+{
+    let lines : Vec<_> = input.lines()
+        .map(Line::new)
+        .filter(|l| l.is_horizontal() || l.is_vertical())
+        .collect();
+
+    count_overlaps_sweep(&lines).to_string()
+}
+
+pub fn solution_b_sweep(input: &str) -> String
+{
+    let lines : Vec<_> = input.lines()
+        .map(Line::new)
+        .collect();
+
+    count_overlaps_sweep(&lines).to_string()
+}
+
 fn count_overlaps(lines : Vec<Line>) -> String
-{    
-    let mut plane : HashMap<(u32, u32), u32> = HashMap::new();
+{
+    let plane = accumulate(&lines);
+    let overlaps = plane.iter()
+        .filter(|(&point, &count)| count > 1)
+        .count();
+
+    overlaps.to_string()
+}
+
+fn accumulate(lines : &[Line]) -> HashMap<Point, u32>
+{
+    let mut plane : HashMap<Point, u32> = HashMap::new();
     for line in lines.iter()
     {
         line.intersect(&mut plane);
     }
+    plane
+}
 
-    let overlaps = plane.iter()
-        .filter(|(&point, &count)| count > 1)
-        .count();
+fn parse_plane(input : &str) -> HashMap<Point, u32>
+{
+    let lines : Vec<_> = input.lines()
+        .map(Line::new)
+        .collect();
+    accumulate(&lines)
+}
 
-    overlaps.to_string()
+fn bounds(plane : &HashMap<Point, u32>) -> (Point, Point)
+{
+    let (min_x, max_x) = plane.keys().map(|p| p.0).minmax().into_option().unwrap_or((0, 0));
+    let (min_y, max_y) = plane.keys().map(|p| p.1).minmax().into_option().unwrap_or((0, 0));
+    ((min_x, min_y), (max_x, max_y))
+}
+
+pub fn render_ascii(input: &str) -> String
+{
+    //Same layout as the puzzle's diagram: '.' for no vents, otherwise the count
+    let plane = parse_plane(input);
+    let ((min_x, min_y), (max_x, max_y)) = bounds(&plane);
+    (min_y..=max_y).map(|y|
+    {
+        (min_x..=max_x).map(|x| match plane.get(&(x, y))
+        {
+            None => '.',
+            Some(&n) => char::from_digit(n, 10).unwrap_or('+')
+        }).collect::<String>()
+    }).join("\n")
+}
+
+pub fn export_heatmap(input: &str) -> String
+{
+    let image = render_heatmap(&parse_plane(input));
+    ["./output/05_heatmap.ppm", "./output/05_heatmap.pgm"].iter()
+        .map(|filename|
+        {
+            image.save(filename).unwrap_or_else(|_| panic!("cannot write file at {}", filename));
+            filename.to_string()
+        })
+        .join("\n")
+}
+
+fn render_heatmap(plane : &HashMap<Point, u32>) -> Image
+{
+    //Huge coordinate ranges are binned down, keeping the densest cell of each bin
+    let ((min_x, min_y), (max_x, max_y)) = bounds(plane);
+    let scale = max(1, (max(max_x - min_x, max_y - min_y) + IMAGE_MAX_DIM) / IMAGE_MAX_DIM);
+    let width = ((max_x - min_x) / scale + 1) as usize;
+    let height = ((max_y - min_y) / scale + 1) as usize;
+
+    let mut bins = vec![0u32; width * height];
+    for (&(x, y), &count) in plane.iter()
+    {
+        let bin = &mut bins[((y - min_y) / scale) as usize * width + ((x - min_x) / scale) as usize];
+        *bin = max(*bin, count);
+    }
+
+    let max_count = bins.iter().copied().max().unwrap_or(0).max(1) as f64;
+    let mut image = Image::new(width, height);
+    for (i, &count) in bins.iter().enumerate()
+    {
+        image.set(i % width, i / width, heat(count as f64 / max_count));
+    }
+    image
 }
 
+pub fn densest_regions(input: &str) -> String
+{
+    let plane = parse_plane(input);
+    densest(&plane, REGION_SIZE, REGION_COUNT).iter()
+        .map(|((x, y), overlaps, total)|
+        {
+            format!("{},{} -> {},{}: {} overlaps, {} total coverage", x, y, x + REGION_SIZE - 1, y + REGION_SIZE - 1, overlaps, total)
+        })
+        .join("\n")
+}
+
+//Top regions of a fixed-size tiling by number of overlapping points
+fn densest(plane : &HashMap<Point, u32>, size : i64, count : usize) -> Vec<(Point, usize, u32)>
+{
+    let mut regions : HashMap<Point, (usize, u32)> = HashMap::new();
+    for (&(x, y), &n) in plane.iter()
+    {
+        let region = regions.entry((x.div_euclid(size) * size, y.div_euclid(size) * size)).or_default();
+        if n > 1 { region.0 += 1; }
+        region.1 += n;
+    }
+
+    regions.into_iter()
+        .map(|(corner, (overlaps, total))| (corner, overlaps, total))
+        .sorted_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)).then(a.0.cmp(&b.0)))
+        .take(count)
+        .collect()
+}
+
+fn count_overlaps_sweep(lines : &[Line]) -> u64
+{
+    let runs : Vec<Run> = lines.iter().map(Line::run).collect();
+
+    //Collinear overlaps: per line, the parameter intervals covered at least twice
+    let mut by_line : HashMap<(Point, i64), Vec<(i64, i64)>> = HashMap::new();
+    for run in runs.iter()
+    {
+        let start = param(run.dir, run.origin);
+        by_line.entry(run.line_key()).or_default().push((start, start + run.steps));
+    }
+    let overlapped : HashMap<(Point, i64), Vec<(i64, i64)>> = by_line.into_iter()
+        .map(|(key, intervals)| (key, covered_twice(intervals)))
+        .filter(|(key, intervals)| !intervals.is_empty())
+        .collect();
+    let collinear_total : i64 = overlapped.values()
+        .flat_map(|intervals| intervals.iter())
+        .map(|(start, end)| end - start + 1)
+        .sum();
+
+    //Crossings: sweep over x, only testing runs whose x extents overlap
+    let mut order : Vec<usize> = (0..runs.len()).collect();
+    order.sort_by_key(|&i| runs[i].origin.0);
+    let mut active : Vec<usize> = Vec::new();
+    let mut crossings : HashMap<Point, HashSet<(Point, i64)>> = HashMap::new();
+    for &i in order.iter()
+    {
+        let run = &runs[i];
+        active.retain(|&j| runs[j].end().0 >= run.origin.0);
+        for &j in active.iter()
+        {
+            let other = &runs[j];
+            let (y_lo, y_hi) = (min(run.origin.1, run.end().1), max(run.origin.1, run.end().1));
+            let (o_lo, o_hi) = (min(other.origin.1, other.end().1), max(other.origin.1, other.end().1));
+            if o_hi < y_lo || y_hi < o_lo
+            {
+                continue;
+            }
+            if let Some(point) = run.crossing(other)
+            {
+                let through = crossings.entry(point).or_default();
+                through.insert(run.line_key());
+                through.insert(other.line_key());
+            }
+        }
+        active.push(i);
+    }
+
+    //A crossing point is new unless a collinear overlap already covers it;
+    //if several lines' overlaps cover it, it has been counted once per line
+    let crossing_total : i64 = crossings.iter()
+        .map(|(&point, keys)|
+        {
+            let covering = keys.iter()
+                .filter(|key| overlapped.get(key).is_some_and(|intervals|
+                {
+                    let t = param(key.0, point);
+                    intervals.iter().any(|&(start, end)| start <= t && t <= end)
+                }))
+                .count() as i64;
+            match covering
+            {
+                0 => 1,
+                n => 1 - n
+            }
+        })
+        .sum();
+
+    (collinear_total + crossing_total) as u64
+}
+
+fn covered_twice(mut intervals : Vec<(i64, i64)>) -> Vec<(i64, i64)>
+{
+    let mut events : Vec<(i64, i32)> = intervals.drain(..)
+        .flat_map(|(start, end)| [(start, 1), (end + 1, -1)])
+        .collect();
+    events.sort();
+
+    let mut result : Vec<(i64, i64)> = Vec::new();
+    let mut depth = 0;
+    let mut open_at = None;
+    for (at, delta) in events
+    {
+        depth += delta;
+        match (depth >= 2, open_at)
+        {
+            (true, None) => open_at = Some(at),
+            (false, Some(start)) =>
+            {
+                if at > start { result.push((start, at - 1)); }
+                open_at = None;
+            }
+            _ => {}
+        }
+    }
+    result
+}
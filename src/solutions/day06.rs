@@ -1,8 +1,40 @@
-fn parse(input : &str, size : usize) -> Vec<u64>
+use super::matrix::{self, Matrix};
+
+pub struct Species
+{
+    pub reset_timer : usize,
+    pub newborn_delay : usize,
+}
+
+impl Species
 {
-    input.split(',')
+    pub const LANTERNFISH : Species = Species { reset_timer: 6, newborn_delay: 2 };
+
+    fn max_timer(&self) -> usize
+    {
+        self.reset_timer + self.newborn_delay
+    }
+
+    //transition[to][from]: how many fish with timer `to` one fish with timer `from` becomes in a day
+    fn transition(&self) -> Matrix
+    {
+        let size = self.max_timer() + 1;
+        let mut transition = vec![vec![0u128; size]; size];
+        for n in 1..size
+        {
+            transition[n-1][n] = 1;
+        }
+        transition[self.reset_timer][0] += 1;
+        transition[self.max_timer()][0] += 1;
+        transition
+    }
+}
+
+fn parse(input : &str, size : usize) -> Vec<u128>
+{
+    input.trim().split(',')
         .map(|s| s.parse::<usize>().unwrap())
-        .fold(vec![0u64; size], |mut pop, n|
+        .fold(vec![0u128; size], |mut pop, n|
         {
             *pop.get_mut(n).unwrap() += 1;
             pop
@@ -19,33 +51,22 @@ pub fn solution_b(input: &str) -> String
     solve(input, 256)
 }
 
-fn solve(input: &str, num_days : u32) -> String
+fn solve(input: &str, num_days : u64) -> String
 {
-    const CYCLE_MAX : usize = 6;
-    const NEW_DELAY : usize = 2;
-    const MAX : usize = CYCLE_MAX + NEW_DELAY;
-
-    let mut population = parse(input, MAX+1);
-    let mut new_population = vec![0; MAX+1];
-
-    for i in 0..num_days
+    match population(input, num_days, &Species::LANTERNFISH, None)
     {
-        for n in 0..population.len()
-        {
-            if n == 0
-            {
-                new_population[MAX] = population[0];
-                new_population[CYCLE_MAX] = population[0];
-            }
-            else
-            {
-                new_population[n-1] += population[n]
-            }
-        }
-        (population, new_population) = (new_population, population);
-        for val in &mut new_population { *val = 0; }
+        Some(pop_count) => pop_count.to_string(),
+        None => "overflow".to_string()
     }
+}
 
-    let pop_count : u64 = population.iter().sum();
-    pop_count.to_string()
-}
\ No newline at end of file
+//Population after num_days in O(log num_days) via powers of the daily transition.
+//Without a modulus, returns None if the count does not fit in a u128; a zero modulus also gives None.
+pub fn population(input: &str, num_days : u64, species : &Species, modulus : Option<u128>) -> Option<u128>
+{
+    let initial = parse(input, species.max_timer() + 1);
+    let transition = matrix::pow(species.transition(), num_days, modulus)?;
+
+    matrix::apply(&transition, &initial, modulus)?.into_iter()
+        .try_fold(0u128, |sum, count| matrix::add(sum, count, modulus))
+}
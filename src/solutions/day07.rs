@@ -1,54 +1,113 @@
-use std::{cmp::min};
+use itertools::Itertools;
 
-fn parse(input : &str) -> Vec<i32>
+fn parse(input : &str) -> Vec<i64>
 {
-    let mut positions : Vec<i32> = input.split(',')
-        .map(|s| s.parse::<i32>())
+    let mut positions : Vec<i64> = input.trim().split(',')
+        .map(|s| s.parse::<i64>())
         .filter_map(|n| n.ok())
         .collect();
     positions.sort();
     positions
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Alignment
+{
+    pub position : i64,
+    pub fuel : u64,
+}
+
+type CostModel = (&'static str, fn(u64) -> Option<u64>);
+
+//Costs return None when the fuel for a distance does not fit in a u64
+pub fn linear(distance : u64) -> Option<u64>
+{
+    Some(distance)
+}
+
+pub fn triangular(distance : u64) -> Option<u64>
+{
+    //Halve whichever factor is even before multiplying, so only a total that is itself too large overflows
+    let next = distance.checked_add(1)?;
+    match distance.is_multiple_of(2)
+    {
+        true => (distance / 2).checked_mul(next),
+        false => distance.checked_mul(next / 2)
+    }
+}
+
+pub fn quadratic(distance : u64) -> Option<u64>
+{
+    distance.checked_mul(distance)
+}
+
 pub fn solution_a(input: &str) -> String
 {
     let positions = parse(input);
-    let median_pos = match positions.len() % 2 == 0
+    match align(&positions, linear)
     {
-        true => positions.len()/2,
-        false => positions.len()/2 + 1
-    };
-    let median = positions[median_pos];
-    let total : u32 = positions.iter()
-        .map(|&n| median.abs_diff(n))
-        .sum();
-
-    total.to_string()
+        Some(best) => best.fuel.to_string(),
+        None => "overflow".to_string()
+    }
 }
 
 pub fn solution_b(input: &str) -> String
 {
     let positions = parse(input);
-    let count = positions.len() as f64;
-    let sum  = positions.iter().sum::<i32>() as f64;
-    let mean = (sum/count).round() as i32;
-    
-    let total_1 = total(&positions, &mean);
-    let total_2 = total(&positions, &(mean-1));
-
-    let min_total = min(total_1, total_2);
+    match align(&positions, triangular)
+    {
+        Some(best) => best.fuel.to_string(),
+        None => "overflow".to_string()
+    }
+}
 
-    min_total.to_string()
+pub fn report(input: &str) -> String
+{
+    let positions = parse(input);
+    let models : [CostModel; 3] = [("linear", linear), ("triangular", triangular), ("quadratic", quadratic)];
+    models.iter()
+        .map(|(name, cost)| match align(&positions, cost)
+        {
+            Some(best) => format!("{}: position {}, fuel {}", name, best.position, best.fuel),
+            None => format!("{}: fuel does not fit in 64 bits", name)
+        })
+        .join("\n")
 }
 
-fn total(positions: &[i32], x: &i32) -> u32
+//Finds the leftmost integer position minimizing total fuel, for any cost that is
+//convex and non-decreasing in distance: the total is then convex in position, so
+//binary searching for where it stops decreasing lands on the exact optimum.
+//Returns None if even the optimal total does not fit in a u64.
+pub fn align(positions : &[i64], cost : impl Fn(u64) -> Option<u64>) -> Option<Alignment>
 {
-    positions.iter()
-        .map(|&n| cost(x.abs_diff(n)))
-        .sum()
+    let Some((&lo, &hi)) = positions.iter().minmax().into_option() else
+    {
+        return Some(Alignment { position: 0, fuel: 0 });
+    };
+
+    let (mut lo, mut hi) = (lo, hi);
+    while lo < hi
+    {
+        let mid = lo + (hi.abs_diff(lo) / 2) as i64;
+        //An overflowing total is larger than any that fits
+        let decreasing = match (total(positions, mid + 1, &cost), total(positions, mid, &cost))
+        {
+            (Some(next), Some(here)) => next < here,
+            (Some(_), None) => true,
+            _ => false
+        };
+        match decreasing
+        {
+            true => lo = mid + 1,
+            false => hi = mid
+        }
+    }
+
+    total(positions, lo, &cost).map(|fuel| Alignment { position: lo, fuel })
 }
 
-fn cost(n : u32) -> u32
+fn total(positions: &[i64], x: i64, cost : &impl Fn(u64) -> Option<u64>) -> Option<u64>
 {
-    n * (n+1) / 2
+    positions.iter()
+        .try_fold(0u64, |sum, &n| sum.checked_add(cost(x.abs_diff(n))?))
 }
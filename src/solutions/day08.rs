@@ -1,4 +1,4 @@
-use std::{collections::{HashSet, HashMap}};
+use itertools::Itertools;
 
 pub fn solution_a(input: &str) -> String
 {
@@ -19,172 +19,309 @@ pub fn solution_a(input: &str) -> String
 
 pub fn solution_b(input: &str) -> String
 {
+    let table = SegmentTable::seven_segment_digits();
     let display_sum: u32 = input.lines()
-        .map(Display::new)
-        .map(|d| d.solve())
+        .map(|line| Display::new(line, &table).unwrap())
+        .map(|d| d.decode(&table, &d.solve(&table).unwrap()).unwrap().parse::<u32>().unwrap())
         .sum();
 
     display_sum.to_string()
 }
 
-const DISPLAY_ZERO: &str = "abcefg";
-const DISPLAY_ONE: &str = "cf";
-const DISPLAY_TWO: &str = "acdeg";
-const DISPLAY_THREE: &str = "acdfg";
-const DISPLAY_FOUR: &str = "bcdf";
-const DISPLAY_FIVE: &str = "abdfg";
-const DISPLAY_SIX: &str = "abdefg";
-const DISPLAY_SEVEN: &str = "acf";
-const DISPLAY_EIGHT: &str = "abcdefg";
-const DISPLAY_NINE: &str = "abcdfg";
+pub fn report(input: &str) -> String
+{
+    report_with(input, &SegmentTable::seven_segment_hex())
+}
+
+pub fn report_with(input: &str, table : &SegmentTable) -> String
+{
+    input.lines()
+        .map(|line| match Display::new(line, table).and_then(|d|
+        {
+            let wiring = d.solve(table)?;
+            let output = d.decode(table, &wiring)?;
+            Ok((wiring, output))
+        })
+        {
+            Ok((wiring, output)) => format!("{} ({})", output, wiring),
+            Err(e) => e.to_string()
+        })
+        .join("\n")
+}
+
+//Seven-segment labels as in the puzzle: a top, b/c upper left/right, d middle,
+//e/f lower left/right, g bottom
+const SEVEN_SEGMENT_DIGITS: [(char, &str); 10] =
+[
+    ('0', "abcefg"), ('1', "cf"), ('2', "acdeg"), ('3', "acdfg"), ('4', "bcdf"),
+    ('5', "abdfg"), ('6', "abdefg"), ('7', "acf"), ('8', "abcdefg"), ('9', "abcdfg"),
+];
+
+//Segment sets are u32 masks
+const MAX_SEGMENTS : usize = 32;
+
+const SEVEN_SEGMENT_HEX: [(char, &str); 6] =
+[
+    ('A', "abcdef"), ('b', "bdefg"), ('C', "abeg"), ('d', "cdefg"), ('E', "abdeg"), ('F', "abde"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WiringError
+{
+    UnknownSegment(char),
+    UnknownPattern(String),
+    Inconsistent,
+    Ambiguous(Wiring, Wiring),
+    //A line without the " | " between signal patterns and output digits
+    MalformedLine(String),
+    NoSegments,
+    TooManySegments(usize),
+}
+
+impl std::fmt::Display for WiringError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            WiringError::UnknownSegment(c) => write!(f, "{:?} is not a segment of the table", c),
+            WiringError::UnknownPattern(pattern) => write!(f, "{} does not light up any symbol", pattern),
+            WiringError::Inconsistent => write!(f, "no wiring fits the patterns"),
+            WiringError::Ambiguous(one, two) => write!(f, "several wirings fit the patterns, e.g. {} and {}", one, two),
+            WiringError::MalformedLine(line) => write!(f, "missing \" | \" in {:?}", line),
+            WiringError::NoSegments => write!(f, "the table has no segments"),
+            WiringError::TooManySegments(count) => write!(f, "{} segments, but at most {} are supported", count, MAX_SEGMENTS),
+        }
+    }
+}
 
+//Which segments light up for each symbol; wires are labelled with the same letters as segments
 #[derive(Debug, Clone)]
-struct Display
+pub struct SegmentTable
 {
-    mixed_clusters: Vec<String>,
-    display_clusters: Vec<String>,
-    segment_maps: HashMap<char, HashSet<char>>
+    segments: Vec<char>,
+    symbols: Vec<(char, u32)>,
 }
 
-impl Display
+impl SegmentTable
 {
-    fn new(display_str: &str) -> Self
+    pub fn new(patterns: &[(char, &str)]) -> Result<Self, WiringError>
     {
-        let parts: Vec<_> = display_str.split(" | ")
-            .filter(|&s| !s.is_empty())
+        let segments : Vec<char> = patterns.iter()
+            .flat_map(|(_, p)| p.chars())
+            .sorted()
+            .dedup()
             .collect();
+        match segments.len()
+        {
+            0 => return Err(WiringError::NoSegments),
+            count if count > MAX_SEGMENTS => return Err(WiringError::TooManySegments(count)),
+            _ => ()
+        }
 
-        let mut mixed_clusters = Self::parse(parts[0]);
-        mixed_clusters.sort_by_key(|s| std::cmp::Reverse(s.len()));
-        let display_clusters = Self::parse(parts[1]);
-
-        let all_segments: HashSet<char> = "abcdefg".chars().collect();
-        let mut segment_maps = HashMap::new();
-        segment_maps.insert('a', all_segments.clone());
-        segment_maps.insert('b', all_segments.clone());
-        segment_maps.insert('c', all_segments.clone());
-        segment_maps.insert('d', all_segments.clone());
-        segment_maps.insert('e', all_segments.clone());
-        segment_maps.insert('f', all_segments.clone());
-        segment_maps.insert('g', all_segments);
-
-        Display { mixed_clusters, display_clusters, segment_maps }
+        let mut table = SegmentTable { segments, symbols: Vec::new() };
+        table.symbols = patterns.iter()
+            .map(|&(symbol, p)| (symbol, table.mask(p).unwrap()))
+            .collect();
+        Ok(table)
     }
 
-    fn parse(part: &str) -> Vec<String>
+    //The built-in tables are known to be valid
+    pub fn seven_segment_digits() -> Self
     {
-        part.split(' ').map(|s|
-        {
-            let mut v : Vec<_> = s.chars().collect();
-            v.sort();
-            v.into_iter().collect::<String>()
-        }).collect()
+        Self::new(&SEVEN_SEGMENT_DIGITS).unwrap()
     }
 
-    fn solve(self) -> u32
+    pub fn seven_segment_hex() -> Self
     {
-        let mut to_consider = vec![self; 1];
-        while let Some(current) = to_consider.pop()
-        {
-            let next = current.expand();
-            for node in next.into_iter()
-            {
-                if node.is_solved()
-                {
-                    return node.map_display_clusters();
-                }
-                else if node.is_valid()
-                {
-                    to_consider.push(node);
-                }                
-            }
-            to_consider.sort_by(|a, b| b.mixed_clusters.cmp(&a.mixed_clusters))  
-        }
-        unreachable!("did not find a solution (should not happen!)");
+        let patterns : Vec<_> = SEVEN_SEGMENT_DIGITS.iter().chain(SEVEN_SEGMENT_HEX.iter()).copied().collect();
+        Self::new(&patterns).unwrap()
     }
 
-    fn expand(mut self) -> Vec<Display>
+    fn mask(&self, pattern: &str) -> Result<u32, WiringError>
     {
-        let mixed_cluster = self.mixed_clusters.pop().unwrap_or_default();
-        let segments : &[&str] = match mixed_cluster.len()
+        pattern.chars().try_fold(0, |mask, c| match self.segments.iter().position(|&s| s == c)
         {
-            0 => &[], //empty
-            2 => &[DISPLAY_ONE],
-            3 => &[DISPLAY_SEVEN],
-            4 => &[DISPLAY_FOUR],
-            5 => &[DISPLAY_TWO, DISPLAY_THREE, DISPLAY_FIVE],
-            6 => &[DISPLAY_ZERO, DISPLAY_SIX, DISPLAY_NINE],
-            7 => &[], //no information since all segments are on
-            _ => unreachable!("invalid input somehow"),
-        };
+            Some(i) => Ok(mask | 1 << i),
+            None => Err(WiringError::UnknownSegment(c))
+        })
+    }
 
-        segments.iter().map(|&orig_segs|
-        {
-            self.update_segment_maps(&mixed_cluster, orig_segs)
-        }).collect()
+    //new ensures there are between 1 and MAX_SEGMENTS segments
+    fn all(&self) -> u32
+    {
+        u32::MAX >> (MAX_SEGMENTS - self.segments.len())
     }
 
-    fn update_segment_maps(&self, mixed_cluster: &str, orig_segs: &str) -> Display
+    fn symbol(&self, mask: u32) -> Option<char>
     {
-        let mut node = self.clone();
+        self.symbols.iter().find(|&&(_, m)| m == mask).map(|&(symbol, _)| symbol)
+    }
+}
 
-        for c1 in mixed_cluster.chars()
-        {
-            let segment = node.segment_maps.get_mut(&c1).unwrap();
-            segment.retain(|&c| orig_segs.contains(c));
-        }
+//Wire i drives segment permutation[i]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Wiring
+{
+    pub wires: Vec<char>,
+    pub permutation: Vec<char>,
+}
 
-        let complement : Vec<_> = "abcdefg".chars().filter(|&c| !mixed_cluster.contains(c)).collect();
-        for c2 in complement
-        {
-            let segment = node.segment_maps.get_mut(&c2).unwrap();
-            segment.retain(|&c| !orig_segs.contains(c));
-        }
-        
-        node
+impl std::fmt::Display for Wiring
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        let pairs = self.wires.iter().zip(self.permutation.iter())
+            .map(|(w, s)| format!("{}->{}", w, s))
+            .join(" ");
+        write!(f, "{}", pairs)
     }
+}
+
+#[derive(Debug, Clone)]
+struct Display
+{
+    signals: Vec<u32>,
+    outputs: Vec<u32>,
+    output_strs: Vec<String>,
+}
 
-    fn map_display_clusters(&self) -> u32
+impl Display
+{
+    fn new(display_str: &str, table: &SegmentTable) -> Result<Self, WiringError>
     {
-        let a = self.map(&self.display_clusters[0]);
-        let b = self.map(&self.display_clusters[1]);
-        let c = self.map(&self.display_clusters[2]);
-        let d = self.map(&self.display_clusters[3]);
+        let (signal_str, output_str) = display_str.split_once(" | ")
+            .ok_or_else(|| WiringError::MalformedLine(display_str.to_string()))?;
+
+        let output_strs : Vec<String> = output_str.split_whitespace().map(|s| s.to_string()).collect();
+        let signals = signal_str.split_whitespace().map(|s| table.mask(s)).collect::<Result<Vec<_>, _>>()?;
+        let outputs = output_strs.iter().map(|s| table.mask(s)).collect::<Result<Vec<_>, _>>()?;
 
-        a*1000 + b*100 + c*10 + d
+        Ok(Display { signals, outputs, output_strs })
     }
 
-    fn map(&self, cluster: &str) -> u32
+    fn solve(&self, table: &SegmentTable) -> Result<Wiring, WiringError>
     {
-        let mut display_chars : Vec<_> = cluster.chars()
-            .map(|c| *self.segment_maps.get(&c).unwrap().iter().next().unwrap())
-            .collect();
-        display_chars.sort();
+        let observed : Vec<u32> = self.signals.iter().chain(self.outputs.iter()).copied().unique().collect();
+        let candidates = vec![table.all(); table.segments.len()];
+
+        let mut found = Vec::new();
+        Self::search(table, &observed, candidates, &mut found);
 
-        let display_str = display_chars.into_iter().collect::<String>();
-        match display_str.as_str()
+        let to_wiring = |assignment : &Vec<u32>| Wiring
         {
-            DISPLAY_ZERO => 0,
-            DISPLAY_ONE => 1,
-            DISPLAY_TWO => 2,
-            DISPLAY_THREE => 3,
-            DISPLAY_FOUR => 4,
-            DISPLAY_FIVE => 5,
-            DISPLAY_SIX => 6,
-            DISPLAY_SEVEN => 7,
-            DISPLAY_EIGHT => 8,
-            DISPLAY_NINE => 9,
-            _ => 0
+            wires: table.segments.clone(),
+            permutation: assignment.iter().map(|m| table.segments[m.trailing_zeros() as usize]).collect()
+        };
+        match found.as_slice()
+        {
+            [] => Err(WiringError::Inconsistent),
+            [one] => Ok(to_wiring(one)),
+            [one, two, ..] => Err(WiringError::Ambiguous(to_wiring(one), to_wiring(two)))
         }
     }
 
-    fn is_solved(&self) -> bool
+    //Backtracks over wires with the fewest remaining candidates, stopping once two solutions prove ambiguity
+    fn search(table: &SegmentTable, observed: &[u32], candidates: Vec<u32>, found: &mut Vec<Vec<u32>>)
     {
-        self.segment_maps.iter().all(|s| s.1.len() == 1)
+        let Some(candidates) = Self::propagate(table, observed, candidates) else { return; };
+
+        let branch = (0..candidates.len())
+            .filter(|&w| candidates[w].count_ones() > 1)
+            .min_by_key(|&w| candidates[w].count_ones());
+        let Some(wire) = branch else
+        {
+            found.push(candidates);
+            return;
+        };
+
+        let mut options = candidates[wire];
+        while options != 0 && found.len() < 2
+        {
+            let segment = options & options.wrapping_neg();
+            options &= !segment;
+            let mut next = candidates.clone();
+            next[wire] = segment;
+            Self::search(table, observed, next, found);
+        }
     }
 
-    fn is_valid(&self) -> bool
+    //Narrows each wire's candidate segments to those allowed by some symbol of the
+    //right size for every observed pattern, until nothing changes
+    fn propagate(table: &SegmentTable, observed: &[u32], mut candidates: Vec<u32>) -> Option<Vec<u32>>
     {
-        self.segment_maps.iter().all(|s| !s.1.is_empty())
+        let all = table.all();
+        let wires = candidates.len();
+        loop
+        {
+            let before = candidates.clone();
+
+            for &pattern in observed.iter()
+            {
+                let compatible : Vec<u32> = table.symbols.iter()
+                    .map(|&(_, m)| m)
+                    .filter(|m| m.count_ones() == pattern.count_ones())
+                    .filter(|&m| (0..wires).all(|w| match pattern & 1 << w != 0
+                    {
+                        true => candidates[w] & m != 0,
+                        false => candidates[w] & !m & all != 0
+                    }))
+                    .collect();
+                if compatible.is_empty()
+                {
+                    return None;
+                }
+
+                let lit = compatible.iter().fold(0, |acc, m| acc | m);
+                let unlit = compatible.iter().fold(0, |acc, m| acc | (!m & all));
+                for (w, candidate) in candidates.iter_mut().enumerate()
+                {
+                    *candidate &= match pattern & 1 << w != 0
+                    {
+                        true => lit,
+                        false => unlit
+                    };
+                }
+            }
+
+            //A segment claimed by one wire is unavailable to the others
+            for w in 0..wires
+            {
+                if candidates[w].count_ones() == 1
+                {
+                    let taken = candidates[w];
+                    for (other, candidate) in candidates.iter_mut().enumerate()
+                    {
+                        if other != w { *candidate &= !taken; }
+                    }
+                }
+            }
+
+            if candidates.contains(&0)
+            {
+                return None;
+            }
+            if candidates == before
+            {
+                return Some(candidates);
+            }
+        }
     }
-}
\ No newline at end of file
+
+    fn decode(&self, table: &SegmentTable, wiring: &Wiring) -> Result<String, WiringError>
+    {
+        let segment_index : Vec<usize> = wiring.permutation.iter()
+            .map(|s| table.segments.iter().position(|x| x == s).unwrap())
+            .collect();
+
+        self.outputs.iter().zip(self.output_strs.iter())
+            .map(|(&pattern, s)|
+            {
+                let mapped = (0..segment_index.len())
+                    .filter(|&w| pattern & 1 << w != 0)
+                    .fold(0, |acc, w| acc | 1 << segment_index[w]);
+                table.symbol(mapped).ok_or_else(|| WiringError::UnknownPattern(s.clone()))
+            })
+            .collect()
+    }
+}
@@ -1,5 +1,5 @@
 use itertools::Itertools;
-use std::{collections::{HashSet}};
+use std::{collections::HashMap, cmp::Ordering};
 
 fn parse(input : &str) -> Vec<Vec<u32>>
 {
@@ -44,40 +44,169 @@ fn get_risk_level(heightmap: &[Vec<u32>], row: usize, col: usize) -> u32
 pub fn solution_b(input: &str) -> String
 {
     let heightmap = parse(input);
-    let basin_score = (0..heightmap.len()).cartesian_product(0..heightmap[0].len())
-        .filter(|(row, col)| get_risk_level(&heightmap, *row, *col) > 0)
-        .map(|(row, col)| get_basin_size(&heightmap, row, col))
+    let basin_score = basins(&heightmap, &BasinOptions::default()).iter()
+        .map(|basin| basin.size())
         .sorted_by(|a, b| b.cmp(a))
         .take(3)
-        .product::<u32>();
+        .product::<usize>();
 
     basin_score.to_string()
 }
 
-fn get_basin_size(heightmap: &[Vec<u32>], row: usize, col: usize) -> u32
+pub fn report(input: &str) -> String
 {
-    let mut basin_points : HashSet<(usize, usize)> = HashSet::new();
-    let mut to_consider : Vec<(usize, usize)> = vec![(row,col)];
-    while let Some(current) = to_consider.pop()
+    let heightmap = parse(input);
+    basins(&heightmap, &BasinOptions::default()).iter()
+        .sorted_by(|a, b| b.size().cmp(&a.size()).then(a.id.cmp(&b.id)))
+        .map(|basin| format!("basin {}: low point {:?}, size {}, heights {}..={}, mean {:.2}",
+            basin.id, basin.low_point, basin.size(), basin.min_height, basin.max_height, basin.mean_height))
+        .join("\n")
+}
+
+pub struct BasinOptions
+{
+    pub wall_height : u32,
+    pub diagonal : bool,
+}
+
+impl Default for BasinOptions
+{
+    fn default() -> Self
     {
-        basin_points.insert(current);
-        let next = expand_node(current, heightmap);
-        for node in next.into_iter().filter(|node| !basin_points.contains(node))
+        BasinOptions { wall_height: 9, diagonal: false }
+    }
+}
+
+#[derive(Debug)]
+pub struct Basin
+{
+    pub id : usize,
+    pub low_point : (usize, usize),
+    pub min_height : u32,
+    pub max_height : u32,
+    pub mean_height : f64,
+    pub cells : Vec<(usize, usize)>,
+}
+
+impl Basin
+{
+    pub fn size(&self) -> usize
+    {
+        self.cells.len()
+    }
+}
+
+pub fn basins(heightmap: &[Vec<u32>], options: &BasinOptions) -> Vec<Basin>
+{
+    let labels = label(heightmap, options);
+    let mut cells : Vec<Vec<(usize, usize)>> = Vec::new();
+    for (row, labels_row) in labels.iter().enumerate()
+    {
+        for (col, id) in labels_row.iter().enumerate()
         {
-            to_consider.push(node);
+            if let &Some(id) = id
+            {
+                if id == cells.len() { cells.push(Vec::new()); }
+                cells[id].push((row, col));
+            }
         }
     }
 
-    basin_points.len().try_into().unwrap()
+    cells.into_iter().enumerate().map(|(id, cells)|
+    {
+        let height = |&(row, col) : &(usize, usize)| heightmap[row][col];
+        let low_point = *cells.iter().min_by_key(|cell| height(cell)).unwrap();
+        let max_height = cells.iter().map(height).max().unwrap();
+        let mean_height = cells.iter().map(height).sum::<u32>() as f64 / cells.len() as f64;
+        Basin { id, low_point, min_height: height(&low_point), max_height, mean_height, cells }
+    }).collect()
 }
 
-fn expand_node((row, col): (usize, usize), heightmap: &[Vec<u32>]) -> Vec<(usize, usize)>
+//Assigns every cell below the wall height a basin id in a single raster sweep,
+//joining each cell with its already-visited neighbours via union-find;
+//ids are numbered in order of each basin's first cell
+pub fn label(heightmap: &[Vec<u32>], options: &BasinOptions) -> Vec<Vec<Option<usize>>>
 {
-    let mut nodes : Vec<(usize, usize)> = Vec::new();
-    if row > 0 { nodes.push((row-1, col)) }
-    if col > 0 { nodes.push((row, col-1)) }
-    if col+1 < heightmap[0].len() { nodes.push((row, col+1)) }
-    if row+1 < heightmap.len() { nodes.push((row+1, col)) }
+    let rows = heightmap.len();
+    let cols = heightmap.first().map_or(0, |r| r.len());
+    let is_open = |row: usize, col: usize| heightmap[row][col] < options.wall_height;
 
-    nodes.into_iter().filter(|(r, c)| heightmap[*r][*c] != 9).collect::<Vec<_>>()
+    let mut sets = DisjointSet::new(rows * cols);
+    for (row, col) in (0..rows).cartesian_product(0..cols).filter(|&(r, c)| is_open(r, c))
+    {
+        let mut earlier = vec![(row.checked_sub(1), Some(col)), (Some(row), col.checked_sub(1))];
+        if options.diagonal
+        {
+            earlier.push((row.checked_sub(1), col.checked_sub(1)));
+            earlier.push((row.checked_sub(1), Some(col+1).filter(|&c| c < cols)));
+        }
+        for (r, c) in earlier.into_iter().filter_map(|(r, c)| Some((r?, c?)))
+        {
+            if is_open(r, c)
+            {
+                sets.union(row * cols + col, r * cols + c);
+            }
+        }
+    }
+
+    let mut ids : HashMap<usize, usize> = HashMap::new();
+    (0..rows).map(|row|
+    {
+        (0..cols).map(|col| match is_open(row, col)
+        {
+            true =>
+            {
+                let root = sets.find(row * cols + col);
+                let next_id = ids.len();
+                Some(*ids.entry(root).or_insert(next_id))
+            }
+            false => None
+        }).collect()
+    }).collect()
+}
+
+struct DisjointSet
+{
+    parent : Vec<usize>,
+    rank : Vec<u8>,
+}
+
+impl DisjointSet
+{
+    fn new(size : usize) -> Self
+    {
+        DisjointSet { parent: (0..size).collect(), rank: vec![0; size] }
+    }
+
+    fn find(&mut self, x : usize) -> usize
+    {
+        let mut root = x;
+        while self.parent[root] != root { root = self.parent[root]; }
+
+        //Path compression
+        let mut x = x;
+        while self.parent[x] != root
+        {
+            let next = self.parent[x];
+            self.parent[x] = root;
+            x = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a : usize, b : usize)
+    {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b { return; }
+        match self.rank[a].cmp(&self.rank[b])
+        {
+            Ordering::Less => self.parent[a] = b,
+            Ordering::Greater => self.parent[b] = a,
+            Ordering::Equal =>
+            {
+                self.parent[b] = a;
+                self.rank[a] += 1;
+            }
+        }
+    }
 }
@@ -1,4 +1,15 @@
 use itertools::Itertools;
+use std::collections::HashMap;
+
+const MAX_REPAIR_EDITS : usize = 3;
+
+//One closer per line: the closer, its corruption points and its completion digit
+const DEFAULT_POLICY : &str = "\
+base 5
+) 3 1
+] 57 2
+} 1197 3
+> 25137 4";
 
 fn parse(input: &str) -> Vec<Chunk>
 {
@@ -7,6 +18,55 @@ fn parse(input: &str) -> Vec<Chunk>
         .collect()
 }
 
+#[derive(Debug, Clone)]
+pub struct Brackets
+{
+    pairs: Vec<(char, char)>
+}
+
+impl Default for Brackets
+{
+    fn default() -> Self
+    {
+        Brackets::new(&[('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')])
+    }
+}
+
+impl Brackets
+{
+    pub fn new(pairs: &[(char, char)]) -> Self
+    {
+        Brackets { pairs: pairs.to_vec() }
+    }
+
+    fn closer_for(&self, c: char) -> Option<char>
+    {
+        self.pairs.iter().find(|&&(open, _)| open == c).map(|&(_, close)| close)
+    }
+
+    fn is_closer(&self, c: char) -> bool
+    {
+        self.pairs.iter().any(|&(_, close)| close == c)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scan
+{
+    Complete,
+    Incomplete { completion: String },
+    //Columns are 1-based; expected is None when nothing was open
+    Corrupted { column: usize, found: char, expected: Option<char> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit
+{
+    Replace { column: usize, with: char },
+    Delete { column: usize },
+    Insert { column: usize, c: char },
+}
+
 #[derive(Debug)]
 struct Chunk
 {
@@ -20,93 +80,253 @@ impl Chunk
         Chunk { tokens: line.to_owned() }
     }
 
-    fn scan_for_error(mut self) -> Option<char>
+    //Single pass with a stack of expected closers; characters outside the bracket set are skipped
+    fn scan(&self, brackets: &Brackets) -> Scan
     {
-        self = self.simplify();        
-        if let Some(pos) = self.tokens.find([')', ']', '}', '>'])
+        let mut expected : Vec<char> = Vec::new();
+        for (i, c) in self.tokens.chars().enumerate()
         {
-            return self.tokens.chars().nth(pos)
+            if let Some(close) = brackets.closer_for(c)
+            {
+                expected.push(close);
+            }
+            else if brackets.is_closer(c)
+            {
+                let top = expected.pop();
+                if top != Some(c)
+                {
+                    return Scan::Corrupted { column: i + 1, found: c, expected: top };
+                }
+            }
         }
-        None
-    }
 
-    fn simplify(mut self) -> Self
-    {
-        let mut prev_len = 0;
-        while prev_len != self.tokens.len()
+        match expected.is_empty()
         {
-            prev_len = self.tokens.len();
-            self.tokens = self.tokens.replace("()", "")
-                .replace("[]", "")
-                .replace("{}", "")
-                .replace("<>", "");
+            true => Scan::Complete,
+            false => Scan::Incomplete { completion: expected.into_iter().rev().collect() }
         }
-        self
     }
 
-    fn validate(mut self) -> Option<Self>
+    //Breadth-first over edits at the current error column, so the first fix found uses the fewest edits
+    fn repair(&self, brackets: &Brackets) -> Option<Vec<Edit>>
     {
-        self = self.simplify();
-        match self.tokens.contains([')', ']', '}', '>'])
+        let mut frontier : Vec<(String, Vec<Edit>)> = vec![(self.tokens.clone(), Vec::new())];
+        for _ in 0..=MAX_REPAIR_EDITS
         {
-            true => None,
-            false => Some(self)
+            let mut next = Vec::new();
+            for (tokens, edits) in frontier
+            {
+                let Scan::Corrupted { column, expected, .. } = Chunk::new(&tokens).scan(brackets) else
+                {
+                    return Some(edits);
+                };
+
+                let mut candidates = vec![Edit::Delete { column }];
+                if let Some(expected) = expected
+                {
+                    candidates.insert(0, Edit::Replace { column, with: expected });
+                    candidates.push(Edit::Insert { column, c: expected });
+                }
+                for edit in candidates
+                {
+                    let mut edits = edits.clone();
+                    let tokens = apply(&tokens, &edit);
+                    edits.push(edit);
+                    next.push((tokens, edits));
+                }
+            }
+            frontier = next;
         }
+        None
     }
+}
 
-    fn complete(self) -> String
+fn apply(tokens: &str, edit: &Edit) -> String
+{
+    let mut chars : Vec<char> = tokens.chars().collect();
+    match *edit
     {
-        self.tokens
-            .replace('(', ")")
-            .replace('[', "]")
-            .replace('{', "}")
-            .replace('<', ">")
-            .chars()
-            .rev()
-            .collect()
+        Edit::Replace { column, with } => chars[column - 1] = with,
+        Edit::Delete { column } => { chars.remove(column - 1); },
+        Edit::Insert { column, c } => chars.insert(column - 1, c),
     }
+    chars.into_iter().collect()
 }
 
 pub fn solution_a(input: &str) -> String
 {
+    solution_a_with(input, &ScoringPolicy::default())
+}
+
+pub fn solution_b(input: &str) -> String
+{
+    solution_b_with(input, &ScoringPolicy::default())
+}
+
+pub fn solution_a_with(input: &str, policy: &ScoringPolicy) -> String
+{
+    let brackets = Brackets::default();
+    if let Err(e) = policy.validate(&brackets)
+    {
+        return e.to_string();
+    }
     let chunks = parse(input);
     let invalid_chunk_sum = chunks.into_iter()
-        .filter_map(|chunk| chunk.scan_for_error())
-        .map(|c| match c
+        .filter_map(|chunk| match chunk.scan(&brackets)
         {
-            ')' => 3,
-            ']' => 57,
-            '}' => 1197,
-            '>' => 25137,
-            _ => unreachable!("error character is incorrect somehow")
-        }).sum::<u32>();
+            Scan::Corrupted { found, .. } => Some(found),
+            _ => None
+        })
+        .map(|c| policy.corruption_score(c))
+        .sum::<u64>();
 
     invalid_chunk_sum.to_string()
 }
 
-pub fn solution_b(input: &str) -> String
+pub fn solution_b_with(input: &str, policy: &ScoringPolicy) -> String
 {
+    let brackets = Brackets::default();
+    if let Err(e) = policy.validate(&brackets)
+    {
+        return e.to_string();
+    }
     let chunks = parse(input);
     let completed_chunk_scores = chunks.into_iter()
-        .filter_map(|chunk| chunk.validate())
-        .map(|chunk| chunk.complete())
-        .map(get_score)
+        .filter_map(|chunk| match chunk.scan(&brackets)
+        {
+            Scan::Incomplete { completion } => Some(completion),
+            _ => None
+        })
+        .map(|completion| policy.completion_score(&completion))
         .sorted()
         .collect::<Vec<_>>();
 
-    let middle_score = completed_chunk_scores[completed_chunk_scores.len()/2];
-    middle_score.to_string()
+    match completed_chunk_scores.get(completed_chunk_scores.len()/2)
+    {
+        Some(middle_score) => middle_score.to_string(),
+        None => "no incomplete lines".to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError
+{
+    InvalidLine(usize, String),
+    MissingBase,
+    MissingCloser(char),
 }
 
-fn get_score(compl_str: String) -> u64
+impl std::fmt::Display for PolicyError
 {
-    compl_str.chars().fold(0, |score, c| match c
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
     {
-        ')' => score * 5 + 1,
-        ']' => score * 5 + 2,
-        '}' => score * 5 + 3,
-        '>' => score * 5 + 4,
-        _ => unreachable!("invalid input")
-    })
+        match self
+        {
+            PolicyError::InvalidLine(line, text) => write!(f, "line {} of the policy is not understood: {:?}", line, text),
+            PolicyError::MissingBase => write!(f, "the policy has no base"),
+            PolicyError::MissingCloser(c) => write!(f, "the policy has no scores for {:?}", c),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScoringPolicy
+{
+    base: u64,
+    corruption: HashMap<char, u64>,
+    completion: HashMap<char, u64>,
 }
 
+impl Default for ScoringPolicy
+{
+    fn default() -> Self
+    {
+        ScoringPolicy::parse(DEFAULT_POLICY).unwrap()
+    }
+}
+
+impl ScoringPolicy
+{
+    //Blank lines and lines starting with '#' are ignored
+    pub fn parse(config: &str) -> Result<Self, PolicyError>
+    {
+        let mut base = None;
+        let mut corruption = HashMap::new();
+        let mut completion = HashMap::new();
+        for (i, line) in config.lines().enumerate()
+        {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#')
+            {
+                continue;
+            }
+
+            let invalid = || PolicyError::InvalidLine(i + 1, line.to_string());
+            let fields : Vec<_> = line.split_whitespace().collect();
+            match fields.as_slice()
+            {
+                ["base", n] => base = Some(n.parse::<u64>().map_err(|_| invalid())?),
+                [closer, points, digit] =>
+                {
+                    let mut chars = closer.chars();
+                    let (Some(c), None) = (chars.next(), chars.next()) else { return Err(invalid()); };
+                    corruption.insert(c, points.parse::<u64>().map_err(|_| invalid())?);
+                    completion.insert(c, digit.parse::<u64>().map_err(|_| invalid())?);
+                }
+                _ => return Err(invalid())
+            }
+        }
+
+        match base
+        {
+            Some(base) => Ok(ScoringPolicy { base, corruption, completion }),
+            None => Err(PolicyError::MissingBase)
+        }
+    }
+
+    //Every closer in the bracket set needs both scores, or totals would silently come out wrong
+    pub fn validate(&self, brackets: &Brackets) -> Result<(), PolicyError>
+    {
+        match brackets.pairs.iter().find(|(_, close)| !self.corruption.contains_key(close) || !self.completion.contains_key(close))
+        {
+            Some(&(_, close)) => Err(PolicyError::MissingCloser(close)),
+            None => Ok(())
+        }
+    }
+
+    pub fn corruption_score(&self, closer: char) -> u64
+    {
+        self.corruption.get(&closer).copied().unwrap_or(0)
+    }
+
+    pub fn completion_score(&self, completion: &str) -> u64
+    {
+        completion.chars().fold(0, |score, c| score * self.base + self.completion.get(&c).copied().unwrap_or(0))
+    }
+}
+
+pub fn lint(input: &str) -> String
+{
+    lint_with(input, &Brackets::default())
+}
+
+pub fn lint_with(input: &str, brackets: &Brackets) -> String
+{
+    parse(input).iter().enumerate()
+        .filter_map(|(i, chunk)| match chunk.scan(brackets)
+        {
+            Scan::Complete => None,
+            Scan::Incomplete { completion } => Some(format!("{}: incomplete, complete with {}", i + 1, completion)),
+            Scan::Corrupted { column, found, expected } =>
+            {
+                let expected = expected.map_or("nothing open".to_string(), |c| format!("expected {}", c));
+                let repair = match chunk.repair(brackets)
+                {
+                    Some(edits) => format!("repair: {:?}", edits),
+                    None => format!("no repair within {} edits", MAX_REPAIR_EDITS)
+                };
+                Some(format!("{}:{}: found {}, {}; {}", i + 1, column, found, expected, repair))
+            }
+        })
+        .join("\n")
+}
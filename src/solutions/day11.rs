@@ -1,18 +1,84 @@
-use std::{collections::{HashMap}, cmp::min};
+use std::collections::HashMap;
 use itertools::Itertools;
+use super::render::{Image, heat, WHITE};
+
+const MAX_STEPS : usize = 100_000;
+const ANIMATION_STEPS : usize = 100;
+const FRAME_CELL_SIZE : usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood
+{
+    Four,
+    Eight,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Topology
+{
+    pub neighbourhood: Neighbourhood,
+    pub wrap: bool,
+    pub flash_threshold: u32,
+}
+
+impl Default for Topology
+{
+    fn default() -> Self
+    {
+        Topology { neighbourhood: Neighbourhood::Eight, wrap: false, flash_threshold: 9 }
+    }
+}
+
+impl Topology
+{
+    fn adjacent(&self, (row, col): (usize, usize), rows: usize, cols: usize) -> Vec<(usize, usize)>
+    {
+        let offsets : &[(i64, i64)] = match self.neighbourhood
+        {
+            Neighbourhood::Four => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Neighbourhood::Eight => &[(-1, -1), (-1, 0), (-1, 1), (0, -1), (0, 1), (1, -1), (1, 0), (1, 1)],
+        };
+
+        //On small toroidal grids several offsets can land on the same cell (or on itself)
+        offsets.iter()
+            .filter_map(|&(d_row, d_col)|
+            {
+                let (r, c) = (row as i64 + d_row, col as i64 + d_col);
+                match self.wrap
+                {
+                    true => Some((r.rem_euclid(rows as i64) as usize, c.rem_euclid(cols as i64) as usize)),
+                    false if (0..rows as i64).contains(&r) && (0..cols as i64).contains(&c) => Some((r as usize, c as usize)),
+                    false => None
+                }
+            })
+            .filter(|&x| x != (row, col))
+            .unique()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Synchronization
+{
+    At(usize),
+    //The states repeat with this period from step pre_period onwards, and none of them is all zero
+    Never { pre_period: usize, period: usize },
+    Unknown { steps: usize },
+}
 
 struct OctopusGrid
 {
     rows: usize,
     cols: usize,
+    threshold: u32,
     octopi: HashMap<(usize,usize),Octopus>
 }
 
 impl OctopusGrid
 {
-    fn new(input: &str) -> Self
-    {     
-        let mut grid = OctopusGrid {rows: 0, cols: 0, octopi: HashMap::new()};
+    fn new(input: &str, topology: &Topology) -> Self
+    {
+        let mut grid = OctopusGrid {rows: 0, cols: 0, threshold: topology.flash_threshold, octopi: HashMap::new()};
 
         let lines = input.lines().collect::<Vec<_>>();
         grid.rows = lines.len();
@@ -23,11 +89,7 @@ impl OctopusGrid
             line.chars().enumerate().for_each(|(col, level)|
             {
                 let energy_level = level.to_digit(10).unwrap();
-                let adj_rows = row.checked_sub(1).unwrap_or_default()..=min(row+1, grid.rows-1);
-                let adj_cols = col.checked_sub(1).unwrap_or_default()..=min(col+1, grid.cols-1);
-                let adjacent_octopi = adj_rows.cartesian_product(adj_cols)
-                    .filter(|&x| x != (row,col))
-                    .collect();
+                let adjacent_octopi = topology.adjacent((row, col), grid.rows, grid.cols);
 
                 let octopus = Octopus { energy_level, is_flashing: false, adjacent_octopi};
                 grid.octopi.insert((row, col), octopus);
@@ -36,8 +98,26 @@ impl OctopusGrid
 
         grid
     }
-    
-    fn step(mut self) -> (Self, u32)
+
+    fn state(&self) -> Vec<u32>
+    {
+        (0..self.rows).cartesian_product(0..self.cols)
+            .map(|cell| self.octopi[&cell].energy_level)
+            .collect()
+    }
+
+    fn is_synchronized(&self) -> bool
+    {
+        self.octopi.values().all(|o| o.energy_level == 0)
+    }
+
+    fn step(self) -> (Self, u32)
+    {
+        self.step_observed(&mut |_| {})
+    }
+
+    //Calls on_wave after each round of flash propagation, before flashed octopi reset
+    fn step_observed(mut self, on_wave: &mut dyn FnMut(&OctopusGrid)) -> (Self, u32)
     {
         let mut to_update : Vec<(usize, usize)> = (0..self.rows).cartesian_product(0..self.cols).collect();
         while !to_update.is_empty()
@@ -45,11 +125,11 @@ impl OctopusGrid
             let mut to_propagate : Vec<(usize, usize)> = Vec::new();
             for cell in to_update.iter()
             {
-                let mut octopus = self.octopi.get_mut(cell).unwrap();
+                let octopus = self.octopi.get_mut(cell).unwrap();
                 if octopus.is_flashing { continue; }
 
                 octopus.energy_level += 1;
-                if octopus.energy_level > 9
+                if octopus.energy_level > self.threshold
                 {
                     octopus.is_flashing = true;
                     octopus.adjacent_octopi.iter().for_each(|adj|
@@ -59,8 +139,9 @@ impl OctopusGrid
                 }
             }
             to_update = to_propagate;
+            on_wave(&self);
         }
-        
+
         let mut num_flashing = 0;
         for octopus in self.octopi.values_mut().filter(|o| o.is_flashing)
         {
@@ -69,7 +150,75 @@ impl OctopusGrid
             octopus.energy_level = 0;
         }
         (self, num_flashing)
-    }    
+    }
+
+    fn frame(&self, step: usize, wave: Option<usize>) -> Frame
+    {
+        let cells = (0..self.rows)
+            .map(|row| (0..self.cols).map(|col| &self.octopi[&(row, col)]).map(|o| (o.energy_level, o.is_flashing)).collect())
+            .collect::<Vec<Vec<_>>>();
+        let flashes = cells.iter().flatten().filter(|(_, flashing)| *flashing).count() as u32;
+        Frame { step, wave, cells, flashes, threshold: self.threshold }
+    }
+}
+
+//Energy levels and flashing state of the grid at one point of the animation
+pub struct Frame
+{
+    pub step: usize,
+    pub wave: Option<usize>,
+    pub cells: Vec<Vec<(u32, bool)>>,
+    pub flashes: u32,
+    threshold: u32,
+}
+
+impl Frame
+{
+    fn title(&self) -> String
+    {
+        match self.wave
+        {
+            Some(wave) => format!("step {} wave {}: {} flashes", self.step, wave, self.flashes),
+            None => format!("step {}: {} flashes", self.step, self.flashes)
+        }
+    }
+
+    fn to_ansi(&self) -> String
+    {
+        let rows = self.cells.iter().map(|row|
+        {
+            row.iter().map(|&(level, flashing)| match (flashing, level)
+            {
+                (true, _) => "\x1b[1;97m*\x1b[0m".to_string(),
+                (false, 0) => "\x1b[1;93m0\x1b[0m".to_string(),
+                (false, n) => format!("\x1b[2m{}\x1b[0m", std::char::from_digit(n.min(9), 10).unwrap())
+            }).collect::<String>()
+        }).join("\n");
+        format!("{}\n{}", self.title(), rows)
+    }
+
+    fn to_image(&self) -> Image
+    {
+        let rows = self.cells.len();
+        let cols = self.cells.first().map_or(0, |r| r.len());
+        let mut image = Image::new(cols * FRAME_CELL_SIZE, rows * FRAME_CELL_SIZE);
+        for (row, cells) in self.cells.iter().enumerate()
+        {
+            for (col, &(level, flashing)) in cells.iter().enumerate()
+            {
+                let colour = match flashing
+                {
+                    true => WHITE,
+                    false => heat(level as f64 / (self.threshold + 1) as f64 * 0.6)
+                };
+                for (y, x) in (0..FRAME_CELL_SIZE).cartesian_product(0..FRAME_CELL_SIZE)
+                {
+                    image.set(col * FRAME_CELL_SIZE + x, row * FRAME_CELL_SIZE + y, colour);
+                }
+            }
+        }
+        image
+    }
 }
 
 struct Octopus
@@ -81,7 +230,7 @@ struct Octopus
 
 pub fn solution_a(input: &str) -> String
 {
-    let mut grid = OctopusGrid::new(input);
+    let mut grid = OctopusGrid::new(input, &Topology::default());
     let mut num_flashes = 0;
     let mut flashing : u32;
     for i in 1..=100
@@ -94,12 +243,90 @@ pub fn solution_a(input: &str) -> String
 
 pub fn solution_b(input: &str) -> String
 {
-    let mut grid = OctopusGrid::new(input);
-    let mut i = 0;
-    while grid.octopi.values().any(|o| o.energy_level != 0)
+    match synchronize(input, &Topology::default(), MAX_STEPS)
+    {
+        Synchronization::At(step) => step.to_string(),
+        Synchronization::Never { pre_period, period } =>
+            format!("never synchronizes (repeats every {} steps from step {})", period, pre_period),
+        Synchronization::Unknown { steps } => format!("did not synchronize within {} steps", steps)
+    }
+}
+
+//Steps until every octopus flashes at once, remembering each state so that
+//a repeat proves the grid is stuck in a cycle that never synchronizes
+pub fn synchronize(input: &str, topology: &Topology, max_steps: usize) -> Synchronization
+{
+    run_until(input, topology, max_steps, OctopusGrid::is_synchronized)
+}
+
+pub fn find_cycle(input: &str, topology: &Topology, max_steps: usize) -> Option<(usize, usize)>
+{
+    match run_until(input, topology, max_steps, |_| false)
+    {
+        Synchronization::Never { pre_period, period } => Some((pre_period, period)),
+        _ => None
+    }
+}
+
+//Steps until the grid satisfies done, or a state repeats, after which it never will
+fn run_until(input: &str, topology: &Topology, max_steps: usize, done: impl Fn(&OctopusGrid) -> bool) -> Synchronization
+{
+    let mut grid = OctopusGrid::new(input, topology);
+    let mut seen : HashMap<Vec<u32>, usize> = HashMap::new();
+    for step in 0..=max_steps
     {
+        if step > 0 && done(&grid)
+        {
+            return Synchronization::At(step);
+        }
+        if let Some(first) = seen.insert(grid.state(), step)
+        {
+            return Synchronization::Never { pre_period: first, period: step - first };
+        }
         (grid, _) = grid.step();
-        i += 1;
     }
-    i.to_string()
-}
\ No newline at end of file
+    Synchronization::Unknown { steps: max_steps }
+}
+
+pub fn animation_ansi(input: &str) -> String
+{
+    animate(input, &Topology::default(), ANIMATION_STEPS, false).iter()
+        .map(|frame| format!("\x1b[2J\x1b[H{}", frame.to_ansi()))
+        .join("\n")
+}
+
+pub fn export_animation(input: &str) -> String
+{
+    let frames = animate(input, &Topology::default(), ANIMATION_STEPS, true);
+    for (i, frame) in frames.iter().enumerate()
+    {
+        let filename = format!("./output/11/frame_{:05}.ppm", i);
+        frame.to_image().save(&filename).unwrap_or_else(|_| panic!("cannot write file at {}", filename));
+    }
+    frames.iter().enumerate()
+        .map(|(i, frame)| format!("frame_{:05}.ppm {}", i, frame.title()))
+        .join("\n")
+}
+
+//One frame per step after flashed octopi reset (counting that step's flashes),
+//preceded by one frame per propagation wave if waves is set
+pub fn animate(input: &str, topology: &Topology, steps: usize, waves: bool) -> Vec<Frame>
+{
+    let mut grid = OctopusGrid::new(input, topology);
+    let mut frames = vec![grid.frame(0, None)];
+    for step in 1..=steps
+    {
+        let mut wave = 0;
+        let flashes;
+        (grid, flashes) = grid.step_observed(&mut |g|
+        {
+            wave += 1;
+            if waves { frames.push(g.frame(step, Some(wave))); }
+        });
+
+        let mut frame = grid.frame(step, None);
+        frame.flashes = flashes;
+        frames.push(frame);
+    }
+    frames
+}
@@ -1,52 +1,254 @@
-use std::collections::{HashMap};
+use std::collections::{HashMap, HashSet};
+use itertools::Itertools;
 
 pub fn solution_a(input: &str) -> String
 {
-    solve(input, false)
+    solve(input, 0)
 }
 
 pub fn solution_b(input: &str) -> String
 {
-    solve(input, true)
+    solve(input, 1)
 }
 
-fn solve(input: &str, can_revisit: bool) -> String
+fn solve(input: &str, revisits: u32) -> String
+{
+    match count_paths(input, revisits)
+    {
+        Ok(count) => count.to_string(),
+        Err(e) => e.to_string()
+    }
+}
+
+//Visited small caves are tracked as bits of a u128
+const MAX_SMALL_CAVES : usize = 128;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CaveError
+{
+    //Two connected large caves allow endless loops, so there is no finite count
+    LargeLoop,
+    TooManySmallCaves(usize),
+    MissingCave(&'static str),
+}
+
+impl std::fmt::Display for CaveError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            CaveError::LargeLoop => write!(f, "infinitely many paths"),
+            CaveError::TooManySmallCaves(count) => write!(f, "{} small caves, but at most {} are supported", count, MAX_SMALL_CAVES),
+            CaveError::MissingCave(name) => write!(f, "there is no {} cave", name),
+        }
+    }
+}
+
+//Paths from start to end visiting small caves at most once, except that up to `revisits`
+//extra visits to small caves (other than start and end) are allowed in total
+pub fn count_paths(input: &str, revisits: u32) -> Result<u64, CaveError>
+{
+    let caves = Caves::new(&parse(input))?;
+    let mut memo = HashMap::new();
+    Ok(caves.count(caves.start, 0, revisits, &mut memo))
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct RouteFilter
+{
+    pub required: Vec<String>,
+    pub forbidden: Vec<String>,
+}
+
+impl RouteFilter
+{
+    fn accepts(&self, route: &[String]) -> bool
+    {
+        self.required.iter().all(|cave| route.contains(cave))
+            && !self.forbidden.iter().any(|cave| route.contains(cave))
+    }
+}
+
+pub fn list_routes(input: &str) -> String
+{
+    match find_routes(input, 0, &RouteFilter::default())
+    {
+        Ok(routes) => routes.iter().map(|route| route.join(",")).join("\n"),
+        Err(e) => e.to_string()
+    }
+}
+
+//Every route under the same rules as count_paths, shortest first
+pub fn find_routes(input: &str, revisits: u32, filter: &RouteFilter) -> Result<Vec<Vec<String>>, CaveError>
+{
+    let caves = Caves::new(&parse(input))?;
+
+    let routes = caves.routes(revisits).into_iter()
+        .map(|route| route.into_iter().map(|id| caves.names[id].clone()).collect::<Vec<_>>())
+        .filter(|route| filter.accepts(route))
+        .sorted_by(|a, b| a.len().cmp(&b.len()).then(a.cmp(b)))
+        .collect();
+    Ok(routes)
+}
+
+//Graphviz DOT of the cave system: small caves as ellipses, large caves as filled boxes,
+//start and end doubled, and the edges and caves of `highlight` drawn in red
+pub fn to_dot(input: &str, highlight: Option<&[String]>) -> String
 {
     let connections = parse(input);
-    let mut valid_paths = 0;
-    let mut to_consider = vec![Path::new(can_revisit)];
-    while let Some(path) = to_consider.pop()
+    let highlight = highlight.unwrap_or(&[]);
+    let highlighted_edges : HashSet<(&str, &str)> = highlight.iter()
+        .tuple_windows()
+        .flat_map(|(a, b)| [(a.as_str(), b.as_str()), (b.as_str(), a.as_str())])
+        .collect();
+
+    let mut dot = vec!["graph caves".to_string(), "{".to_string()];
+    for &name in connections.keys().sorted()
     {
-        let Some(next) = connections.get(path.prev()) else { continue };
-        for &node in next
+        let shape = match (name, name.chars().all(|c| c.is_lowercase()))
+        {
+            ("start" | "end", _) => "shape=doublecircle",
+            (_, true) => "shape=ellipse",
+            (_, false) => "shape=box, style=filled, fillcolor=lightgrey"
+        };
+        let colour = match highlight.iter().any(|cave| cave == name)
+        {
+            true => ", color=red, penwidth=2",
+            false => ""
+        };
+        dot.push(format!("    \"{}\" [{}{}];", name, shape, colour));
+    }
+    for (a, b) in connections.iter()
+        .flat_map(|(&a, next)| next.iter().map(move |&b| (a, b)))
+        .filter(|(a, b)| a < b)
+        .sorted()
+    {
+        let colour = match highlighted_edges.contains(&(a, b))
+        {
+            true => " [color=red, penwidth=2]",
+            false => ""
+        };
+        dot.push(format!("    \"{}\" -- \"{}\"{};", a, b, colour));
+    }
+    dot.push("}".to_string());
+    dot.join("\n")
+}
+
+//Only small caves other than start and end can be revisited, so only they get a bit in `bits`;
+//every other cave's bit is 0
+struct Caves
+{
+    names: Vec<String>,
+    is_small: Vec<bool>,
+    bits: Vec<u128>,
+    adjacent: Vec<Vec<usize>>,
+    start: usize,
+    end: usize,
+}
+
+impl Caves
+{
+    fn new(connections: &HashMap<&str, Vec<&str>>) -> Result<Self, CaveError>
+    {
+        let mut names : Vec<String> = connections.keys().map(|s| s.to_string()).collect();
+        names.sort();
+
+        let id = |name: &str| names.iter().position(|n| n == name);
+        let start = id("start").ok_or(CaveError::MissingCave("start"))?;
+        let end = id("end").ok_or(CaveError::MissingCave("end"))?;
+        let adjacent = names.iter()
+            .map(|name| connections[name.as_str()].iter().map(|n| id(n).unwrap()).collect())
+            .collect();
+        let is_small : Vec<bool> = names.iter().map(|name| name.chars().all(|c| c.is_lowercase())).collect();
+
+        let tracked = (0..names.len()).filter(|&c| is_small[c] && c != start && c != end).count();
+        if tracked > MAX_SMALL_CAVES
+        {
+            return Err(CaveError::TooManySmallCaves(tracked));
+        }
+        let mut next_bit = 0;
+        let bits = (0..names.len()).map(|c| match is_small[c] && c != start && c != end
+        {
+            true => { next_bit += 1; 1u128 << (next_bit - 1) }
+            false => 0
+        }).collect();
+
+        let caves = Caves { names, is_small, bits, adjacent, start, end };
+        match caves.has_large_loop()
+        {
+            true => Err(CaveError::LargeLoop),
+            false => Ok(caves)
+        }
+    }
+
+    fn has_large_loop(&self) -> bool
+    {
+        (0..self.names.len()).any(|a| !self.is_small[a] && self.adjacent[a].iter().any(|&b| !self.is_small[b]))
+    }
+
+    //Number of ways to reach the end from node, given the small caves already visited
+    fn count(&self, node: usize, visited: u128, revisits: u32, memo: &mut HashMap<(usize, u128, u32), u64>) -> u64
+    {
+        if node == self.end
+        {
+            return 1;
+        }
+        if let Some(&count) = memo.get(&(node, visited, revisits))
+        {
+            return count;
+        }
+
+        let mut count = 0;
+        for &next in self.adjacent[node].iter()
         {
             //Don't backtrack to the start node
-            if node == "start"
+            if next == self.start
             {
                 continue;
             }
 
-            //If we reach the end, we're done
-            if node == "end"
+            let is_revisit = visited & self.bits[next] != 0;
+            if is_revisit && revisits == 0
             {
-                valid_paths += 1;
                 continue;
             }
+            count += self.count(next, visited | self.bits[next], revisits - is_revisit as u32, memo);
+        }
 
-            //Do not revisit small caves (unless in part b, then we can revisit exactly one cave twice)
-            let node_is_small_cave = node.chars().all(|c| c.is_lowercase());
-            let is_revisit = node_is_small_cave && path.contains(node);
-            if is_revisit && !path.can_revisit
+        memo.insert((node, visited, revisits), count);
+        count
+    }
+
+    //Same rules as count, but keeps every route that reaches the end
+    fn routes(&self, revisits: u32) -> Vec<Vec<usize>>
+    {
+        let mut routes = Vec::new();
+        let mut to_consider = vec![(vec![self.start], 0u128, revisits)];
+        while let Some((route, visited, revisits)) = to_consider.pop()
+        {
+            let node = *route.last().unwrap();
+            if node == self.end
             {
+                routes.push(route);
                 continue;
             }
 
-            let new_path = path.append(node.to_owned(), is_revisit);
-            to_consider.push(new_path);
+            for &next in self.adjacent[node].iter()
+            {
+                let is_revisit = visited & self.bits[next] != 0;
+                if next == self.start || (is_revisit && revisits == 0)
+                {
+                    continue;
+                }
+
+                let mut route = route.clone();
+                route.push(next);
+                to_consider.push((route, visited | self.bits[next], revisits - is_revisit as u32));
+            }
         }
+        routes
     }
-
-    valid_paths.to_string()
 }
 
 fn parse(input: &str) -> HashMap<&str, Vec<&str>>
@@ -75,40 +277,3 @@ fn parse(input: &str) -> HashMap<&str, Vec<&str>>
             map
         })
 }
-
-#[derive(Clone)]
-struct Path
-{
-    data: Vec<String>,
-    can_revisit : bool
-}
-
-impl Path
-{
-    fn new(can_revisit: bool) -> Self
-    {
-        let start_node = String::from("start");
-        let data = vec![start_node];
-
-        Path { data, can_revisit }
-    }
-
-    fn contains(&self, node: &str) -> bool
-    {
-        self.data.iter().any(|s| *s == *node)
-    }
-
-    fn prev(&self) -> &str
-    {
-        self.data.last().unwrap().as_str()
-    }
-
-    fn append(&self, node: String, is_revisit: bool) -> Path
-    {
-        let mut new_path = self.clone();
-        new_path.data.push(node);
-        new_path.can_revisit &= !is_revisit;
-
-        new_path
-    }
-}
@@ -1,78 +1,179 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, cmp::Ordering, fs, path::Path};
+use itertools::Itertools;
+use super::ocr;
 
+const SVG_CELL_SIZE : i64 = 10;
+
+#[derive(Debug, Clone, Copy)]
 enum FoldType
 {
     X,
     Y
 }
 
+impl FoldType
+{
+    fn coord(self, (x, y): (i64, i64)) -> i64
+    {
+        match self
+        {
+            FoldType::X => x,
+            FoldType::Y => y
+        }
+    }
+
+    fn with_coord(self, (x, y): (i64, i64), coord: i64) -> (i64, i64)
+    {
+        match self
+        {
+            FoldType::X => (coord, y),
+            FoldType::Y => (x, coord)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 struct Fold
 {
     fold_type: FoldType,
-    value: u32
+    value: i64
+}
+
+//What undoing a fold needs besides the folded sheet: the largest index across the fold,
+//the dots on or past the fold line, and the dots before it that a folded dot landed on
+struct FoldRecord
+{
+    fold: Fold,
+    max_index: i64,
+    folded: HashSet<(i64, i64)>,
+    covered: HashSet<(i64, i64)>
 }
 
+//rows and cols are the largest row and column index on the sheet
 struct Foldable
 {
-    rows : u32,
-    cols : u32,
-    points: HashSet<(u32, u32)>
+    rows : i64,
+    cols : i64,
+    points: HashSet<(i64, i64)>,
+    history: Vec<FoldRecord>
 }
 
 impl Foldable
 {
     fn new() -> Self
     {
-        Foldable { rows: 0, cols: 0, points: HashSet::new() }
+        Foldable { rows: 0, cols: 0, points: HashSet::new(), history: Vec::new() }
     }
 
-    fn fold_along(self, fold: &Fold) -> Self
+    fn fold_along(mut self, fold: &Fold) -> Self
     {
+        let axis = fold.fold_type;
+        let (folded, kept) : (HashSet<_>, HashSet<_>) = self.points.iter()
+            .partition(|&&p| axis.coord(p) >= fold.value);
+        let covered = folded.iter()
+            .map(|&p| axis.with_coord(p, 2*fold.value - axis.coord(p)))
+            .filter(|p| kept.contains(p))
+            .collect();
+        let max_index = match axis
+        {
+            FoldType::X => self.cols,
+            FoldType::Y => self.rows
+        };
+        self.history.push(FoldRecord { fold: *fold, max_index, folded, covered });
+
         match fold.fold_type
         {
             FoldType::X => self.fold_along_x(fold.value),
-            FoldType::Y => self.fold_along_y(fold.value)            
+            FoldType::Y => self.fold_along_y(fold.value)
         }
     }
 
-    fn fold_along_x(mut self, value: u32) -> Self
+    //Works out the sheet before the last fold from the sheet after it and the fold record
+    fn undo(&mut self) -> Option<Fold>
     {
-        let mut new_points : HashSet<(u32, u32)> = HashSet::new();
-        for (x, y) in self.points
+        let record = self.history.pop()?;
+        let (axis, value) = (record.fold.fold_type, record.fold.value);
+        let (shift, _) = Self::fold_extent(record.max_index, value);
+
+        //A dot before the line only moved by the shift, unless all that landed there was a folded dot
+        let kept : HashSet<_> = self.points.iter()
+            .map(|&p| axis.with_coord(p, axis.coord(p) - shift))
+            .filter(|&p| axis.coord(p) >= 0)
+            .filter(|&p| record.covered.contains(&p) || !record.folded.contains(&axis.with_coord(p, 2*value - axis.coord(p))))
+            .collect();
+        self.points = kept.union(&record.folded).copied().collect();
+        match axis
         {
-            if x < value
-            { 
-                new_points.insert((x,y));
-            }
-            else
-            {
-                let new_x = 2*value - x;
-                new_points.insert((new_x, y));
-            }
+            FoldType::X => self.cols = record.max_index,
+            FoldType::Y => self.rows = record.max_index
         }
-        self.cols = value - 1;
-        self.points = new_points;
+        Some(record.fold)
+    }
+
+    fn fold_along_x(mut self, value: i64) -> Self
+    {
+        let (shift, new_cols) = Self::fold_extent(self.cols, value);
+        self.points = self.points.iter()
+            .filter_map(|&(x, y)| Self::reflect(x, value, shift).map(|x| (x, y)))
+            .collect();
+        self.cols = new_cols;
+        self
+    }
+
+    fn fold_along_y(mut self, value: i64) -> Self
+    {
+        let (shift, new_rows) = Self::fold_extent(self.rows, value);
+        self.points = self.points.iter()
+            .filter_map(|&(x, y)| Self::reflect(y, value, shift).map(|y| (x, y)))
+            .collect();
+        self.rows = new_rows;
         self
     }
 
-    fn fold_along_y(mut self, value: u32) -> Self
+    //When the folded-over part is longer than the part it lands on, it sticks out past 0,
+    //so everything shifts along by the difference to keep coordinates non-negative
+    fn fold_extent(max_index: i64, value: i64) -> (i64, i64)
     {
-        let mut new_points : HashSet<(u32, u32)> = HashSet::new();
-        for (x, y) in self.points
+        let kept = value;
+        let folded = max_index - value;
+        ((folded - kept).max(0), kept.max(folded) - 1)
+    }
+
+    //Dots on the fold line itself disappear
+    fn reflect(coord: i64, value: i64, shift: i64) -> Option<i64>
+    {
+        match coord.cmp(&value)
         {
-            if y < value
-            { 
-                new_points.insert((x,y));
-            }
-            else
+            Ordering::Less => Some(coord + shift),
+            Ordering::Greater => Some(2*value - coord + shift),
+            Ordering::Equal => None
+        }
+    }
+
+    fn to_svg(&self, next_fold: Option<&Fold>) -> String
+    {
+        let (width, height) = ((self.cols + 1) * SVG_CELL_SIZE, (self.rows + 1) * SVG_CELL_SIZE);
+        let mut svg = vec![
+            format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\">", width, height),
+            format!("<rect width=\"{}\" height=\"{}\" fill=\"white\" stroke=\"black\"/>", width, height),
+        ];
+        for (x, y) in self.points.iter().sorted()
+        {
+            svg.push(format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"black\"/>",
+                x * SVG_CELL_SIZE, y * SVG_CELL_SIZE, SVG_CELL_SIZE, SVG_CELL_SIZE));
+        }
+        if let Some(fold) = next_fold
+        {
+            let at = fold.value * SVG_CELL_SIZE + SVG_CELL_SIZE / 2;
+            let (x1, y1, x2, y2) = match fold.fold_type
             {
-                let new_y = 2*value - y;
-                new_points.insert((x, new_y));
-            }
+                FoldType::X => (at, 0, at, height),
+                FoldType::Y => (0, at, width, at)
+            };
+            svg.push(format!("<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"red\" stroke-dasharray=\"4\"/>", x1, y1, x2, y2));
         }
-        self.rows = value - 1;
-        self.points = new_points;
-        self
+        svg.push("</svg>".to_string());
+        svg.join("\n")
     }
 
     fn stringify(&self) -> String
@@ -102,8 +203,8 @@ fn parse(input: &str) -> (Foldable, Vec<Fold>)
     let foldable = parts.0.lines().fold(Foldable::new(), |mut foldable, s|
     {
         let (x_str, y_str) = s.split_once(',').unwrap();
-        let x = x_str.parse::<u32>().unwrap();
-        let y = y_str.parse::<u32>().unwrap();
+        let x = x_str.parse::<i64>().unwrap();
+        let y = y_str.parse::<i64>().unwrap();
 
         if x > foldable.cols { foldable.cols = x; }
         if y > foldable.rows { foldable.rows = y; }
@@ -122,7 +223,7 @@ fn parse(input: &str) -> (Foldable, Vec<Fold>)
             "y" => FoldType::Y,
             _ => unreachable!("invalid fold input somehow")
         };
-        let value = fold_data.1.parse::<u32>().unwrap();
+        let value = fold_data.1.parse::<i64>().unwrap();
 
         Fold{fold_type, value}
     }).collect::<Vec<_>>();
@@ -143,6 +244,39 @@ pub fn solution_b(input: &str) -> String
     for fold in folds
     {
         foldable = foldable.fold_along(&fold);
-    }    
-    foldable.stringify()
+    }
+    ocr::recognize(foldable.points.iter().copied()).unwrap_or_else(|| foldable.stringify())
+}
+
+pub fn export_svgs(input: &str) -> String
+{
+    let (mut foldable, folds) = parse(input);
+    let mut filenames = Vec::new();
+    for i in 0..=folds.len()
+    {
+        let filename = format!("./output/13/sheet_{:02}.svg", i);
+        let path = Path::new(&filename);
+        fs::create_dir_all(path.parent().unwrap()).unwrap_or_else(|_| panic!("cannot create directory for {}", filename));
+        fs::write(path, foldable.to_svg(folds.get(i))).unwrap_or_else(|_| panic!("cannot write file at {}", filename));
+        filenames.push(filename);
+
+        if let Some(fold) = folds.get(i)
+        {
+            foldable = foldable.fold_along(fold);
+        }
+    }
+    filenames.join("\n")
+}
+
+//Folds everything, then unfolds again from the fold records and checks the original sheet comes back
+pub fn check_unfold(input: &str) -> String
+{
+    let (mut foldable, folds) = parse(input);
+    let original = (foldable.rows, foldable.cols, foldable.points.clone());
+    for fold in folds.iter()
+    {
+        foldable = foldable.fold_along(fold);
+    }
+    while foldable.undo().is_some() {}
+    ((foldable.rows, foldable.cols, foldable.points) == original).to_string()
 }
@@ -1,65 +1,161 @@
-use std::{collections::HashMap};
+use std::collections::{HashMap, HashSet};
 use itertools::Itertools;
+use super::matrix::{self, Matrix};
 
+const CHECK_STEPS : usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleError
+{
+    MissingSeparator,
+    Malformed { line: usize, text: String },
+    Duplicate { pair: (char, char), first_line: usize, second_line: usize },
+    UnknownElement { element: char, line: usize },
+    Missing { pair: (char, char) },
+}
+
+impl std::fmt::Display for RuleError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            RuleError::MissingSeparator => write!(f, "expected a blank line between the template and the rules"),
+            RuleError::Malformed { line, text } => write!(f, "rule {} is not of the form AB -> C: {:?}", line, text),
+            RuleError::Duplicate { pair: (a, b), first_line, second_line } =>
+                write!(f, "pair {}{} has rules on both line {} and line {}", a, b, first_line, second_line),
+            RuleError::UnknownElement { element, line } =>
+                write!(f, "rule {} inserts {}, which is not in the template or any rule's pair", line, element),
+            RuleError::Missing { pair: (a, b) } => write!(f, "pair {}{} can occur but has no rule", a, b),
+        }
+    }
+}
+
+//Elements are interned to ids 0..n, and the pair (a, b) to the dense index a*n + b
 #[derive(Debug)]
 struct Polymer
 {
-    state: HashMap<String, u64>,
-    rules: HashMap<String, (String, String)>
+    elements: Vec<char>,
+    template: Vec<usize>,
+    insertions: Vec<Option<usize>>,
+    transition: Matrix,
 }
 
 impl Polymer
 {
-    fn new(input: &str) -> Self
+    fn new(input: &str) -> Result<Self, RuleError>
     {
-        let (template_str, rules_str) = input.split_once("\r\n\r\n").unwrap();
+        let (template_str, rules_str) = input.split_once("\r\n\r\n").ok_or(RuleError::MissingSeparator)?;
 
-        let mut state = HashMap::new();
-        let template_chars = template_str.chars().collect::<Vec<_>>();
-        for i in 0..template_str.len()-1
+        //Rules are numbered by their line in the rules section, starting at 1
+        let mut rules : HashMap<(char, char), (char, usize)> = HashMap::new();
+        for (i, s) in rules_str.lines().enumerate().filter(|(_, s)| !s.trim().is_empty())
         {
-            let pair = format!("{}{}", template_chars[i], template_chars[i+1]);
-            let &count = state.get(&pair).unwrap_or(&0);
-            state.insert(pair, count + 1);
+            let line = i + 1;
+            let malformed = || RuleError::Malformed { line, text: s.to_string() };
+            let (rule_from, rule_to) = s.split_once(" -> ").ok_or_else(malformed)?;
+            let (Some((a, b)), Some(c)) = (rule_from.chars().collect_tuple(), rule_to.chars().exactly_one().ok()) else
+            {
+                return Err(malformed());
+            };
+            if let Some(&(_, first_line)) = rules.get(&(a, b))
+            {
+                return Err(RuleError::Duplicate { pair: (a, b), first_line, second_line: line });
+            }
+            rules.insert((a, b), (c, line));
         }
 
-        let rules = rules_str.lines().fold(HashMap::new(), |mut map, s|
+        let elements : Vec<char> = template_str.chars()
+            .chain(rules.keys().flat_map(|&(a, b)| [a, b]))
+            .sorted()
+            .dedup()
+            .collect();
+        if let Some((_, &(element, line))) = rules.iter()
+            .sorted_by_key(|(_, &(_, line))| line)
+            .find(|(_, (c, _))| elements.binary_search(c).is_err())
         {
-            let (rule_from, rule_to) = s.split_once(" -> ").unwrap();
-            let first_char = &rule_from[0..1];
-            let second_char = &rule_from[1..];
-            let rule_1 = format!("{}{}", first_char, rule_to);
-            let rule_2 = format!("{}{}", rule_to, second_char);
-            map.insert(rule_from.to_owned(), (rule_1, rule_2));
-            map
-        });
-
-        Polymer { state, rules }
-    }
+            return Err(RuleError::UnknownElement { element, line });
+        }
 
-    fn step(mut self) -> Self
-    {
-        self.state = self.state.iter().fold(HashMap::new(), |mut map, (pair, &count)|
+        //Only pairs that can actually appear need a rule
+        let mut reachable : Vec<(char, char)> = template_str.chars().tuple_windows().collect();
+        let mut seen : HashSet<(char, char)> = reachable.iter().copied().collect();
+        while let Some((a, b)) = reachable.pop()
         {
-            let (rule_1, rule_2) = self.rules.get(pair).cloned().unwrap();
-            if rule_1 == rule_2
+            let Some(&(c, _)) = rules.get(&(a, b)) else { return Err(RuleError::Missing { pair: (a, b) }); };
+            for pair in [(a, c), (c, b)]
             {
-                let new_count = 2 * (map.get(&rule_1).unwrap_or(&0) + count);
-                map.insert(rule_1, new_count);
+                if seen.insert(pair) { reachable.push(pair); }
             }
-            else
-            {
-                let count_1 = map.get(&rule_1).unwrap_or(&0) + count;
-                let count_2 = map.get(&rule_2).unwrap_or(&0) + count;
-                map.insert(rule_1, count_1);
-                map.insert(rule_2, count_2);
-            }
-            map
-        });
-        self
+        }
+
+        let id = |c: char| elements.binary_search(&c).unwrap();
+        let template = template_str.chars().map(id).collect();
+
+        //transition[to][from]: pair `from` becomes pairs (a, inserted) and (inserted, b) each step
+        let n = elements.len();
+        let mut insertions = vec![None; n * n];
+        let mut transition = vec![vec![0u128; n * n]; n * n];
+        for (&(a, b), &(c, _)) in rules.iter()
+        {
+            let (a, b, inserted) = (id(a), id(b), id(c));
+            insertions[a * n + b] = Some(inserted);
+            transition[a * n + inserted][a * n + b] += 1;
+            transition[inserted * n + b][a * n + b] += 1;
+        }
+
+        Ok(Polymer { elements, template, insertions, transition })
+    }
+
+    fn pair_counts(&self, steps: u64, modulus: Option<u128>) -> Option<Vec<u128>>
+    {
+        let n = self.elements.len();
+        let mut initial = vec![0u128; n * n];
+        for (&a, &b) in self.template.iter().tuple_windows()
+        {
+            initial[a * n + b] += 1;
+        }
+
+        let transition = matrix::pow(self.transition.clone(), steps, modulus)?;
+        matrix::apply(&transition, &initial, modulus)
+    }
+
+    fn histograms(&self, steps: u64, modulus: Option<u128>) -> Option<Histograms>
+    {
+        let n = self.elements.len();
+        let pair_counts = self.pair_counts(steps, modulus)?;
+
+        //Every element starts exactly one pair except the last one, which never changes
+        let mut element_counts = vec![0u128; n];
+        if let Some(&last) = self.template.last()
+        {
+            element_counts[last] = 1;
+        }
+        for (i, &count) in pair_counts.iter().enumerate()
+        {
+            element_counts[i / n] = matrix::add(element_counts[i / n], count, modulus)?;
+        }
+
+        let elements = element_counts.iter().enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(e, &count)| (self.elements[e], count))
+            .collect();
+        let pairs = pair_counts.iter().enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(i, &count)| ((self.elements[i / n], self.elements[i % n]), count))
+            .collect();
+        Some(Histograms { elements, pairs })
     }
 }
 
+//Non-zero counts after some number of steps, in element order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histograms
+{
+    pub elements: Vec<(char, u128)>,
+    pub pairs: Vec<((char, char), u128)>,
+}
+
 pub fn solution_a(input: &str) -> String
 {
     solve(input, 10)
@@ -70,27 +166,121 @@ pub fn solution_b(input: &str) -> String
     solve(input, 40)
 }
 
-fn solve(input: &str, steps: u32) -> String
+//Counts after any number of steps in O(log steps) matrix products. Without a modulus,
+//gives None once the counts no longer fit in a u128.
+pub fn histograms(input: &str, steps: u64, modulus: Option<u128>) -> Result<Option<Histograms>, RuleError>
+{
+    Ok(Polymer::new(input)?.histograms(steps, modulus))
+}
+
+//Lazily yields the polymer after `steps` insertion steps, one element at a time,
+//holding only a stack of pairs still to expand rather than the whole string
+pub struct Expansion
+{
+    polymer: Polymer,
+    pending: Vec<(usize, usize, usize)>,
+    first: Option<usize>,
+}
+
+impl Iterator for Expansion
+{
+    type Item = char;
+
+    fn next(&mut self) -> Option<char>
+    {
+        if let Some(first) = self.first.take()
+        {
+            return Some(self.polymer.elements[first]);
+        }
+
+        let n = self.polymer.elements.len();
+        while let Some((a, b, depth)) = self.pending.pop()
+        {
+            match (depth, self.polymer.insertions[a * n + b])
+            {
+                (0, _) | (_, None) => return Some(self.polymer.elements[b]),
+                (depth, Some(c)) =>
+                {
+                    self.pending.push((c, b, depth - 1));
+                    self.pending.push((a, c, depth - 1));
+                }
+            }
+        }
+        None
+    }
+}
+
+pub fn expand(input: &str, steps: usize) -> Result<Expansion, RuleError>
+{
+    let polymer = Polymer::new(input)?;
+    let first = polymer.template.first().copied();
+    let pending = polymer.template.windows(2)
+        .rev()
+        .map(|pair| (pair[0], pair[1], steps))
+        .collect();
+    Ok(Expansion { polymer, pending, first })
+}
+
+pub fn validate(input: &str) -> String
+{
+    match Polymer::new(input)
+    {
+        Ok(polymer) => format!("ok: {} elements, {} rules", polymer.elements.len(), polymer.insertions.iter().flatten().count()),
+        Err(e) => e.to_string()
+    }
+}
+
+//Streams the explicit polymer for the first few steps and checks its counts against the histograms
+pub fn check_expansion(input: &str) -> String
 {
-    let mut polymer = Polymer::new(input);
-    for i in 0..steps { polymer = polymer.step(); }
+    (0..=CHECK_STEPS).map(|steps|
+    {
+        let expansion = match expand(input, steps)
+        {
+            Ok(expansion) => expansion,
+            Err(e) => return e.to_string()
+        };
+
+        let mut elements : HashMap<char, u128> = HashMap::new();
+        let mut pairs : HashMap<(char, char), u128> = HashMap::new();
+        let mut prev = None;
+        let mut length = 0;
+        for c in expansion
+        {
+            *elements.entry(c).or_default() += 1;
+            if let Some(p) = prev { *pairs.entry((p, c)).or_default() += 1; }
+            prev = Some(c);
+            length += 1;
+        }
 
-    let char_counts = polymer.state.into_iter().fold(HashMap::new(), |mut map, (pair, count)|
+        //Explicit expansions this short never overflow
+        let expected = match histograms(input, steps as u64, None)
+        {
+            Ok(expected) => expected.unwrap(),
+            Err(e) => return e.to_string()
+        };
+        let matches = expected.elements.iter().all(|(c, count)| elements.get(c) == Some(count))
+            && expected.pairs.iter().all(|(pair, count)| pairs.get(pair) == Some(count))
+            && expected.elements.len() == elements.len()
+            && expected.pairs.len() == pairs.len();
+        format!("step {}: length {}, {}", steps, length, if matches { "matches" } else { "MISMATCH" })
+    }).join("\n")
+}
+
+fn solve(input: &str, steps: u64) -> String
+{
+    let histograms = match histograms(input, steps, None)
     {
-        let char = pair.chars().next().unwrap();
-        let char_count = map.get(&char).unwrap_or(&0) + count;
-        map.insert(char, char_count);
-        map
-    }).into_iter()
-        .sorted_by(|&(char_a, char_a_count), (char_b, char_b_count)| char_a_count.cmp(char_b_count))
-        .collect::<Vec<_>>();
-
-    let (least_char, mut least_count) = char_counts[0];
-    let (most_char, mut most_count) = char_counts[char_counts.len()-1];
-    let last_char_of_input = input.lines().next().unwrap().chars().last().unwrap();
-    if least_char == last_char_of_input { least_count += 1; }
-    if most_char == last_char_of_input { most_count += 1; }
+        Ok(Some(histograms)) => histograms,
+        Ok(None) => return "overflow".to_string(),
+        Err(e) => return e.to_string()
+    };
+    let (least_count, most_count) = histograms.elements.iter()
+        .map(|&(_, count)| count)
+        .minmax()
+        .into_option()
+        .unwrap();
     let result = most_count - least_count;
 
     result.to_string()
-}
\ No newline at end of file
+}
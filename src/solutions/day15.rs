@@ -1,4 +1,6 @@
-use std::{collections::{BinaryHeap}, cmp::Ordering};
+use std::{collections::{BinaryHeap, HashSet}, cmp::Ordering};
+use itertools::Itertools;
+use super::render::{Image, heat};
 
 pub fn solution_a(input: &str) -> String
 {
@@ -13,30 +15,212 @@ pub fn solution_b(input: &str) -> String
     solve(grid)
 }
 
+//Wraps the puzzle's way: each tile to the right or down adds one, and values above 9 wrap back to 1
+pub fn wrap_nine(val: u32, tile_x: usize, tile_y: usize) -> u32
+{
+    match val as usize + tile_x + tile_y
+    {
+        0 => 0,
+        sum => ((sum - 1) % 9 + 1) as u32
+    }
+}
+
+pub type TileTransform = fn(u32, usize, usize) -> u32;
+
+pub struct TileOptions
+{
+    pub x_mult: usize,
+    pub y_mult: usize,
+    pub transform: TileTransform,
+    pub diagonal: bool,
+    //A lower bound on every tiled cell's risk for the search heuristic. It must never
+    //exceed a real cell; None uses 0, which is always safe but searches more cells.
+    pub min_risk: Option<u32>,
+}
+
+impl Default for TileOptions
+{
+    fn default() -> Self
+    {
+        TileOptions { x_mult: 1, y_mult: 1, transform: wrap_nine, diagonal: false, min_risk: None }
+    }
+}
+
+pub fn lowest_risk(input: &str, options: &TileOptions) -> u64
+{
+    let grid = Grid::new(input).tiled(options);
+    let search = Search::new(&grid);
+    search.risk[search.index(grid.end())]
+}
+
 fn solve(grid: Grid) -> String
 {
-    let mut to_consider = BinaryHeap::from_iter([Path::new()]);
-    let mut risk : Vec<Vec<u32>> = vec![vec![u32::MAX; grid.len_y]; grid.len_x];
-    while let Some(path) = to_consider.pop()
+    let search = Search::new(&grid);
+    let least_risk = search.risk[search.index(grid.end())];
+    least_risk.to_string()
+}
+
+pub fn route(input: &str) -> String
+{
+    let grid = Grid::new(input);
+    let path = Search::new(&grid).path(&grid);
+    path.iter().map(|(x, y)| format!("{},{}", x, y)).join(" ")
+}
+
+//The grid with every cell off the lowest-risk path blanked out
+pub fn render_route(input: &str) -> String
+{
+    let grid = Grid::new(input);
+    let on_path : HashSet<(usize, usize)> = Search::new(&grid).path(&grid).into_iter().collect();
+    (0..grid.len_x).map(|x|
+    {
+        (0..grid.len_y).map(|y| match on_path.contains(&(x, y))
+        {
+            true => std::char::from_digit(grid.value(x, y).unwrap(), 10).unwrap_or('+'),
+            false => '.'
+        }).collect::<String>()
+    }).join("\n")
+}
+
+pub fn export_route(input: &str) -> String
+{
+    export(Grid::new(input), "15")
+}
+
+pub fn export_route_expanded(input: &str) -> String
+{
+    export(Grid::new(input).expand(5, 5), "15_expanded")
+}
+
+//Writes the path drawn over the grid's risk levels, and a heatmap of the lowest total risk to reach each cell
+fn export(grid: Grid, name: &str) -> String
+{
+    let search = Search::complete(&grid);
+    let path = search.path(&grid);
+
+    let mut overlay = Image::new(grid.len_y, grid.len_x);
+    for (x, y) in (0..grid.len_x).cartesian_product(0..grid.len_y)
+    {
+        let shade = 255 - (grid.value(x, y).unwrap().min(10) * 25) as u8;
+        overlay.set(y, x, (shade, shade, shade));
+    }
+    for &(x, y) in path.iter()
+    {
+        overlay.set(y, x, (255, 0, 0));
+    }
+
+    let max_risk = search.risk.iter().copied().filter(|&risk| risk != UNREACHED).max().unwrap_or(0).max(1);
+    let mut heatmap = Image::new(grid.len_y, grid.len_x);
+    for (x, y) in (0..grid.len_x).cartesian_product(0..grid.len_y)
+    {
+        let risk = search.risk[search.index((x, y))];
+        if risk != UNREACHED
+        {
+            heatmap.set(y, x, heat(risk as f64 / max_risk as f64));
+        }
+    }
+
+    [(overlay, "route"), (heatmap, "risk")].iter()
+        .map(|(image, kind)|
+        {
+            let filename = format!("./output/{}_{}.ppm", name, kind);
+            image.save(&filename).unwrap_or_else(|_| panic!("cannot write file at {}", filename));
+            filename
+        })
+        .join("\n")
+}
+
+const UNREACHED : u64 = u64::MAX;
+
+//A* from the top left; risk holds the lowest known total risk to each cell (UNREACHED if none yet)
+//and prev the index of the cell it was reached from. Both cover every cell of the grid, indexed by
+//x * len_y + y, so only the cell values of a tiled grid are virtual, not the search state.
+struct Search
+{
+    len_y : usize,
+    risk : Vec<u64>,
+    prev : Vec<usize>,
+}
+
+impl Search
+{
+    //Stops once the end's risk is final; cells behind the frontier may still hold tentative risks
+    fn new(grid: &Grid) -> Self
+    {
+        Search::run(grid, true)
+    }
+
+    //Runs until the heap is empty, so every reachable cell's risk is final
+    fn complete(grid: &Grid) -> Self
+    {
+        Search::run(grid, false)
+    }
+
+    fn run(grid: &Grid, stop_at_end: bool) -> Self
     {
-        for next in path.next(&grid)
+        let cells = grid.len_x * grid.len_y;
+        let mut search = Search { len_y: grid.len_y, risk: vec![UNREACHED; cells], prev: vec![usize::MAX; cells] };
+        search.risk[0] = 0;
+
+        let mut to_consider = BinaryHeap::from_iter([Path::new()]);
+        while let Some(path) = to_consider.pop()
         {
-            if next.risk < risk[next.loc_x][next.loc_y]
+            //The heuristic never overestimates, so the first time the end comes off the heap its risk is final
+            if stop_at_end && (path.loc_x, path.loc_y) == grid.end()
+            {
+                break;
+            }
+            let from = search.index((path.loc_x, path.loc_y));
+            if path.risk > search.risk[from]
+            {
+                continue;
+            }
+
+            for next in path.next(grid)
             {
-                risk[next.loc_x][next.loc_y] = next.risk;
-                to_consider.push(next);
+                let to = search.index((next.loc_x, next.loc_y));
+                if next.risk < search.risk[to]
+                {
+                    search.risk[to] = next.risk;
+                    search.prev[to] = from;
+                    to_consider.push(next);
+                }
             }
         }
+        search
+    }
+
+    fn index(&self, (x, y): (usize, usize)) -> usize
+    {
+        x * self.len_y + y
+    }
+
+    fn path(&self, grid: &Grid) -> Vec<(usize, usize)>
+    {
+        let mut path = vec![grid.end()];
+        let mut at = self.index(grid.end());
+        while self.prev[at] != usize::MAX
+        {
+            at = self.prev[at];
+            path.push((at / self.len_y, at % self.len_y));
+        }
+        path.reverse();
+        path
     }
-    let least_risk = risk[grid.len_x-1][grid.len_y-1];
-    least_risk.to_string()
 }
 
+//The base tile repeated x_mult by y_mult times; cell values are computed on demand
+//rather than stored, so expansions cost nothing up front
 struct Grid
 {
     points : Vec<Vec<u32>>,
+    tile_x : usize,
+    tile_y : usize,
     len_x : usize,
     len_y : usize,
+    transform : TileTransform,
+    diagonal : bool,
+    min_risk : u32,
 }
 
 impl Grid
@@ -52,35 +236,41 @@ impl Grid
         }).collect::<Vec<_>>();
         let len_x = points.len();
         let len_y = points[0].len();
-        Grid { points, len_x, len_y }
+        let min_risk = points.iter().flatten().copied().min().unwrap_or(0);
+        Grid { points, tile_x: len_x, tile_y: len_y, len_x, len_y, transform: wrap_nine, diagonal: false, min_risk }
+    }
+
+    fn expand(self, x_mult: usize, y_mult: usize) -> Self
+    {
+        //wrap_nine only gives 0 for a 0 in the base tile, and at least 1 otherwise
+        let min_risk = Some(self.min_risk.min(1));
+        self.tiled(&TileOptions { x_mult, y_mult, min_risk, ..TileOptions::default() })
+    }
+
+    fn tiled(mut self, options: &TileOptions) -> Self
+    {
+        self.len_x = self.tile_x * options.x_mult;
+        self.len_y = self.tile_y * options.y_mult;
+        self.transform = options.transform;
+        self.diagonal = options.diagonal;
+
+        self.min_risk = options.min_risk.unwrap_or(0);
+        self
     }
 
-    fn expand(mut self, x_mult: usize, y_mult: usize) -> Self
+    fn value(&self, x: usize, y: usize) -> Option<u32>
     {
-        let mut new_points = vec![vec![0; 5*self.len_y]; 5*self.len_x];
-        for x in 0..self.points.len()
+        if x >= self.len_x || y >= self.len_y
         {
-            for y in 0..self.points[0].len()
-            {
-                let val = self.points[x][y];
-                for mx in 0..x_mult
-                {
-                    for my in 0..y_mult
-                    {
-                        let mut new_val = val + ((mx + my) as u32);
-                        while new_val > 9 { new_val -= 9; }
-
-                        let new_x = x + mx * self.len_x;
-                        let new_y = y + my * self.len_y;
-                        new_points[new_x][new_y] = new_val;
-                    }
-                }
-            }
+            return None;
         }
-        self.points = new_points;
-        self.len_x *= 5;
-        self.len_y *= 5;
-        self
+        let val = self.points[x % self.tile_x][y % self.tile_y];
+        Some((self.transform)(val, x / self.tile_x, y / self.tile_y))
+    }
+
+    fn end(&self) -> (usize, usize)
+    {
+        (self.len_x-1, self.len_y-1)
     }
 }
 
@@ -89,8 +279,8 @@ struct Path
 {
     loc_x : usize,
     loc_y : usize,
-    risk : u32,
-    heuristic: u32,
+    risk : u64,
+    heuristic: u64,
 }
 
 impl Path
@@ -102,23 +292,39 @@ impl Path
 
     fn next(&self, grid: &Grid) -> Vec<Path>
     {
-        [
+        let mut next = vec![
             self.step(grid, 0, -1), //Up
             self.step(grid, 1, 0), //Right
             self.step(grid, 0, 1), //Down
             self.step(grid, -1, 0) //Left
-        ].into_iter().flatten().collect::<Vec<_>>()
+        ];
+        if grid.diagonal
+        {
+            next.extend([
+                self.step(grid, -1, -1),
+                self.step(grid, 1, -1),
+                self.step(grid, 1, 1),
+                self.step(grid, -1, 1)
+            ]);
+        }
+        next.into_iter().flatten().collect::<Vec<_>>()
     }
 
     fn step(&self, grid: &Grid, x: i32, y: i32) -> Option<Path>
     {
-        let loc_x = ((self.loc_x as i32) + x) as usize;
-        let loc_y = ((self.loc_y as i32) + y) as usize;
-        let Some(&val) = grid.points.get(loc_x).and_then(|x| x.get(loc_y)) else { return None; };
+        let loc_x = self.loc_x.checked_add_signed(x as isize)?;
+        let loc_y = self.loc_y.checked_add_signed(y as isize)?;
+        let val = grid.value(loc_x, loc_y)?;
 
-        let risk = self.risk + val;
-        let dist_to_end = grid.len_x - loc_x + grid.len_y - loc_y;
-        let heuristic = risk + dist_to_end as u32;
+        //Every remaining step costs at least the grid's lowest risk, so this never overestimates
+        let risk = self.risk + val as u64;
+        let (d_x, d_y) = (grid.len_x - 1 - loc_x, grid.len_y - 1 - loc_y);
+        let steps_to_end = match grid.diagonal
+        {
+            true => d_x.max(d_y),
+            false => d_x + d_y
+        };
+        let heuristic = risk + steps_to_end as u64 * grid.min_risk as u64;
 
         Some(Path { loc_x, loc_y, risk, heuristic })
     }
@@ -139,4 +345,3 @@ impl PartialOrd for Path
         Some(self.cmp(other))
     }
 }
-
@@ -1,155 +1,814 @@
-struct BitStream
+use itertools::Itertools;
+use super::bignum::BigUint;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BitsError
 {
-    raw_data : String
+    InvalidHex { position: usize, found: char },
+    Truncated { at_bit: usize, needed: usize, available: usize },
+    Arity { at_bit: usize, type_id: u64, found: usize },
+    FieldOverflow { field: &'static str, bits: usize, value: u64 },
+    //An operator packet being encoded with the wrong number of sub-packets
+    OperandCount { type_id: u64, found: usize },
 }
 
-impl BitStream
+impl std::fmt::Display for BitsError
 {
-    fn from_hex(input: &str) -> Self
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
     {
-        let raw_data = input.chars().fold(String::new(), |s, c| s + &format!("{:04b}", c.to_digit(16).unwrap()));
-        BitStream { raw_data }
+        match self
+        {
+            BitsError::InvalidHex { position, found } => write!(f, "invalid hex digit {:?} at position {}", found, position),
+            BitsError::Truncated { at_bit, needed, available } =>
+                write!(f, "truncated at bit {}: needed {} bits but only {} remain", at_bit, needed, available),
+            BitsError::Arity { at_bit, type_id, found } =>
+                write!(f, "operator packet of type {} at bit {} has {} sub-packets", type_id, at_bit, found),
+            BitsError::FieldOverflow { field, bits, value } => write!(f, "{} {} does not fit in {} bits", field, value, bits),
+            BitsError::OperandCount { type_id, found } =>
+                write!(f, "cannot encode an operator packet of type {} with {} sub-packets", type_id, found),
+        }
     }
+}
+
+fn decode_hex(input: &str) -> Result<Vec<u8>, BitsError>
+{
+    let digits = input.chars().enumerate()
+        .map(|(position, c)| c.to_digit(16).map(|d| d as u8).ok_or(BitsError::InvalidHex { position, found: c }))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(digits.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)).collect())
+}
 
+//A cursor over the bits [pos, end) of some bytes, most significant bit first.
+//Sub-streams share the bytes and keep absolute bit positions for error reporting.
+struct BitStream<'a>
+{
+    bytes : &'a [u8],
+    pos : usize,
+    end : usize,
+}
+
+impl<'a> BitStream<'a>
+{
+    fn new(bytes: &'a [u8], num_bits: usize) -> Self
+    {
+        BitStream { bytes, pos: 0, end: num_bits }
+    }
+
+    fn remaining(&self) -> usize
+    {
+        self.end - self.pos
+    }
+
+    fn bit(&self, i: usize) -> u64
+    {
+        ((self.bytes[i / 8] >> (7 - i % 8)) & 1) as u64
+    }
+
+    //Whether anything other than zero padding is left
     fn any(&self) -> bool
     {
-        !self.raw_data.is_empty() && !self.raw_data.chars().all(|c| c == '0')
+        (self.pos..self.end).any(|i| self.bit(i) == 1)
+    }
+
+    fn check(&self, num_bits: usize) -> Result<(), BitsError>
+    {
+        match num_bits <= self.remaining()
+        {
+            true => Ok(()),
+            false => Err(BitsError::Truncated { at_bit: self.pos, needed: num_bits, available: self.remaining() })
+        }
     }
 
-    fn read(&mut self, num_bits: usize) -> String
+    fn read_as_bin(&mut self, num_bits: usize) -> Result<u64, BitsError>
     {
-        let raw_data = self.raw_data.clone();
-        self.raw_data = raw_data[num_bits..].to_owned();
-        raw_data[..num_bits].to_owned()
+        self.check(num_bits)?;
+        let value = (self.pos..self.pos + num_bits).fold(0, |value, i| value << 1 | self.bit(i));
+        self.pos += num_bits;
+        Ok(value)
     }
 
-    fn read_as_bin(&mut self, num_bits: usize) -> u64
+    fn sub_stream(&mut self, num_bits: usize) -> Result<BitStream<'a>, BitsError>
     {
-        let raw_data = self.raw_data.clone();
-        self.raw_data = raw_data[num_bits..].to_owned();
-        u64::from_str_radix(&raw_data[..num_bits], 2).unwrap()
+        self.check(num_bits)?;
+        let substream = BitStream { bytes: self.bytes, pos: self.pos, end: self.pos + num_bits };
+        self.pos += num_bits;
+        Ok(substream)
     }
 
-    fn parse(&mut self) -> Vec<Packet>
+    fn parse(&mut self) -> Result<Vec<Packet>, BitsError>
     {
         let mut packets: Vec<Packet> = Vec::new();
-        while self.any()
+        //There is always an outermost packet, so empty or all-zero input fails wherever reading it does
+        while packets.is_empty() || self.any()
         {
-            let next = self.parse_next_packet();
+            let next = self.parse_next_packet()?;
             packets.push(next);
         }
-        packets
+        Ok(packets)
     }
 
-    fn parse_next_packet(&mut self) -> Packet
+    fn parse_next_packet(&mut self) -> Result<Packet, BitsError>
     {
-        let version = self.read_as_bin(3);
-        let type_id = self.read_as_bin(3);
-        let data = match type_id
+        let start = self.pos;
+        let version = self.read_as_bin(3)?;
+        let type_id = self.read_as_bin(3)?;
+        let data = match Operator::from_type_id(type_id)
         {
-            4 => self.parse_literal_data(),
-            _ => self.parse_operator_data()
+            None => self.parse_literal_data()?,
+            Some(operator) =>
+            {
+                let subpackets = self.parse_operator_data()?;
+                if !operator.accepts(subpackets.len())
+                {
+                    return Err(BitsError::Arity { at_bit: start, type_id, found: subpackets.len() });
+                }
+                PacketData::Operator(operator, subpackets)
+            }
         };
-        Packet { version, type_id, data }
+        Ok(Packet { version, data })
     }
-    
-    fn parse_literal_data(&mut self) -> PacketData
+
+    //Literals can have any number of groups; whether they fit is up to whoever evaluates them
+    fn parse_literal_data(&mut self) -> Result<PacketData, BitsError>
     {
-        let mut value_str = String::new();
+        let mut groups = Vec::new();
         loop
         {
-            let continue_bit = self.read(1);
-            value_str += &self.read(4);
-            if continue_bit == "0" { break; }
+            let continue_bit = self.read_as_bin(1)?;
+            groups.push(self.read_as_bin(4)? as u8);
+            if continue_bit == 0 { break; }
         }
-        let value = u64::from_str_radix(&value_str, 2).unwrap();
-        PacketData::Literal(value)
+        Ok(PacketData::Literal(BigUint::from_nibbles(&groups)))
     }
 
-    fn parse_operator_data(&mut self) -> PacketData
+    fn parse_operator_data(&mut self) -> Result<Vec<Packet>, BitsError>
     {
-        let length_type_id = self.read_as_bin(1);
-        let packets = match length_type_id
+        let length_type_id = self.read_as_bin(1)?;
+        match length_type_id
         {
             0 => self.parse_from_bit_length(),
             _ => self.parse_from_num_packets()
-        };    
-        PacketData::Subpackets(packets)
+        }
     }
 
-    fn parse_from_bit_length(&mut self) -> Vec<Packet>
+    fn parse_from_bit_length(&mut self) -> Result<Vec<Packet>, BitsError>
     {
-        let num_bits = self.read_as_bin(15) as usize;
-        let data_to_parse = self.read(num_bits);
-        let mut substream = BitStream { raw_data: data_to_parse };
-        substream.parse()
+        let num_bits = self.read_as_bin(15)? as usize;
+        let mut substream = self.sub_stream(num_bits)?;
+        let mut packets = Vec::new();
+        while substream.remaining() > 0
+        {
+            packets.push(substream.parse_next_packet()?);
+        }
+        Ok(packets)
     }
 
-    fn parse_from_num_packets(&mut self) -> Vec<Packet>
+    fn parse_from_num_packets(&mut self) -> Result<Vec<Packet>, BitsError>
     {
-        let num_packets = self.read_as_bin(11) as usize;
+        let num_packets = self.read_as_bin(11)? as usize;
         let mut packets = Vec::new();
         while packets.len() < num_packets
         {
-            let packet = self.parse_next_packet();
+            let packet = self.parse_next_packet()?;
             packets.push(packet);
         }
-        packets
+        Ok(packets)
     }
+}
 
+//Every type id except 4, which marks a literal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator
+{
+    Sum,
+    Product,
+    Minimum,
+    Maximum,
+    GreaterThan,
+    LessThan,
+    EqualTo,
 }
 
-struct Packet
+const OPERATORS : [Operator; 7] = [
+    Operator::Sum, Operator::Product, Operator::Minimum, Operator::Maximum,
+    Operator::GreaterThan, Operator::LessThan, Operator::EqualTo
+];
+
+const LITERAL_TYPE_ID : u64 = 4;
+
+impl Operator
 {
-    version: u64,
-    type_id: u64,
-    data: PacketData
+    pub fn from_type_id(type_id: u64) -> Option<Self>
+    {
+        OPERATORS.iter().copied().find(|op| op.type_id() == type_id)
+    }
+
+    pub fn type_id(self) -> u64
+    {
+        match self
+        {
+            Operator::Sum => 0,
+            Operator::Product => 1,
+            Operator::Minimum => 2,
+            Operator::Maximum => 3,
+            Operator::GreaterThan => 5,
+            Operator::LessThan => 6,
+            Operator::EqualTo => 7,
+        }
+    }
+
+    pub fn name(self) -> &'static str
+    {
+        match self
+        {
+            Operator::Sum => "sum",
+            Operator::Product => "product",
+            Operator::Minimum => "min",
+            Operator::Maximum => "max",
+            Operator::GreaterThan => "gt",
+            Operator::LessThan => "lt",
+            Operator::EqualTo => "eq",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self>
+    {
+        OPERATORS.iter().copied().find(|op| op.name() == name)
+    }
+
+    //Comparisons take exactly two operands, every other operator at least one
+    pub fn accepts(self, operands: usize) -> bool
+    {
+        match self
+        {
+            Operator::GreaterThan | Operator::LessThan | Operator::EqualTo => operands == 2,
+            _ => operands > 0
+        }
+    }
+
+    fn from_symbol(symbol: &str) -> Option<Self>
+    {
+        OPERATORS.iter().copied().find(|op| op.infix_symbol() == Some(symbol))
+    }
+
+    fn infix_symbol(self) -> Option<&'static str>
+    {
+        match self
+        {
+            Operator::Sum => Some("+"),
+            Operator::Product => Some("*"),
+            Operator::GreaterThan => Some(">"),
+            Operator::LessThan => Some("<"),
+            Operator::EqualTo => Some("=="),
+            Operator::Minimum | Operator::Maximum => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet
+{
+    pub version: u64,
+    pub data: PacketData
 }
 
-enum PacketData
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PacketData
 {
-    Literal(u64),
-    Subpackets(Vec<Packet>)
+    Literal(BigUint),
+    Operator(Operator, Vec<Packet>)
 }
 
 impl Packet
 {
-    fn evaluate(&self) -> u64
+    pub fn type_id(&self) -> u64
+    {
+        match &self.data
+        {
+            PacketData::Literal(_) => LITERAL_TYPE_ID,
+            PacketData::Operator(operator, _) => operator.type_id()
+        }
+    }
+
+    pub fn evaluate<N: Number>(&self) -> Result<N, EvalError>
+    {
+        self.evaluate_at(&mut Vec::new())
+    }
+
+    //path holds the child indices leading from the outermost packet to this one
+    fn evaluate_at<N: Number>(&self, path: &mut Vec<usize>) -> Result<N, EvalError>
     {
-        let values = match &self.data
+        let (operator, subpackets) = match &self.data
         {
-            PacketData::Subpackets(s) => s.iter().map(|p| p.evaluate()).collect(),
-            _ => Vec::new()
+            PacketData::Literal(value) =>
+            {
+                return N::from_literal(value).ok_or_else(|| EvalError::LiteralOverflow { path: path.clone(), number: N::NAME });
+            }
+            PacketData::Operator(operator, subpackets) => (*operator, subpackets)
+        };
+        let mut values : Vec<N> = Vec::with_capacity(subpackets.len());
+        for (i, subpacket) in subpackets.iter().enumerate()
+        {
+            path.push(i);
+            values.push(subpacket.evaluate_at(path)?);
+            path.pop();
+        }
+
+        let overflow = || EvalError::Overflow { path: path.clone(), operator, number: N::NAME };
+        match (operator, values.as_slice())
+        {
+            (Operator::Sum, [first, rest @ ..]) =>
+                rest.iter().try_fold(first.clone(), |acc, v| acc.checked_add(v)).ok_or_else(overflow),
+            (Operator::Product, [first, rest @ ..]) =>
+                rest.iter().try_fold(first.clone(), |acc, v| acc.checked_mul(v)).ok_or_else(overflow),
+            (Operator::Minimum, [first, rest @ ..]) => Ok(rest.iter().fold(first, |min, v| min.min(v)).clone()),
+            (Operator::Maximum, [first, rest @ ..]) => Ok(rest.iter().fold(first, |max, v| max.max(v)).clone()),
+            (Operator::GreaterThan, [a, b]) => Ok(N::from_u64((a > b) as u64)),
+            (Operator::LessThan, [a, b]) => Ok(N::from_u64((a < b) as u64)),
+            (Operator::EqualTo, [a, b]) => Ok(N::from_u64((a == b) as u64)),
+            (_, values) => Err(EvalError::Arity { path: path.clone(), operator, found: values.len() })
+        }
+    }
+
+    //S-expression form, e.g. (sum 1 (max 3 7)); with versions, each node gets an @version suffix
+    pub fn to_sexpr(&self, versions: bool) -> String
+    {
+        let version = match versions
+        {
+            true => format!("@{}", self.version),
+            false => String::new()
         };
+        match &self.data
+        {
+            PacketData::Literal(value) => format!("{}{}", value, version),
+            PacketData::Operator(operator, subpackets) =>
+            {
+                let operands = subpackets.iter().map(|p| format!(" {}", p.to_sexpr(versions))).collect::<String>();
+                format!("({}{}{})", operator.name(), version, operands)
+            }
+        }
+    }
+
+    //Arithmetic form, e.g. 1 + max(3, 7); every nested infix operator is parenthesised.
+    //Versions are left out, so parse_infix reads them back as 0.
+    pub fn to_infix(&self) -> String
+    {
+        self.infix(true)
+    }
+
+    fn infix(&self, top: bool) -> String
+    {
+        match &self.data
+        {
+            PacketData::Literal(value) => value.to_string(),
+            PacketData::Operator(operator, subpackets) => match operator.infix_symbol()
+            {
+                Some(symbol) if subpackets.len() > 1 =>
+                {
+                    let expr = subpackets.iter().map(|p| p.infix(false)).join(&format!(" {} ", symbol));
+                    match top
+                    {
+                        true => expr,
+                        false => format!("({})", expr)
+                    }
+                }
+                _ => format!("{}({})", operator.name(), subpackets.iter().map(|p| p.infix(true)).join(", "))
+            }
+        }
+    }
+}
+
+//The values packets can be evaluated as; the arithmetic is checked, so a result that
+//does not fit is reported rather than wrapped
+pub trait Number: Clone + Ord + std::fmt::Display
+{
+    const NAME : &'static str;
+    fn from_literal(value: &BigUint) -> Option<Self>;
+    fn from_u64(value: u64) -> Self;
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+}
+
+impl Number for u64
+{
+    const NAME : &'static str = "u64";
+
+    fn from_literal(value: &BigUint) -> Option<Self>
+    {
+        value.to_u64()
+    }
+
+    fn from_u64(value: u64) -> Self
+    {
+        value
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self>
+    {
+        u64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>
+    {
+        u64::checked_mul(*self, *other)
+    }
+}
+
+impl Number for u128
+{
+    const NAME : &'static str = "u128";
+
+    fn from_literal(value: &BigUint) -> Option<Self>
+    {
+        value.to_u128()
+    }
+
+    fn from_u64(value: u64) -> Self
+    {
+        value as u128
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self>
+    {
+        u128::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>
+    {
+        u128::checked_mul(*self, *other)
+    }
+}
+
+impl Number for BigUint
+{
+    const NAME : &'static str = "unbounded integer";
+
+    fn from_literal(value: &BigUint) -> Option<Self>
+    {
+        Some(value.clone())
+    }
+
+    fn from_u64(value: u64) -> Self
+    {
+        BigUint::from(value)
+    }
+
+    fn checked_add(&self, other: &Self) -> Option<Self>
+    {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self>
+    {
+        Some(self * other)
+    }
+}
+
+//Paths are the child indices from the outermost packet, so [2, 0] is the first sub-packet of its third
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EvalError
+{
+    Overflow { path: Vec<usize>, operator: Operator, number: &'static str },
+    LiteralOverflow { path: Vec<usize>, number: &'static str },
+    Arity { path: Vec<usize>, operator: Operator, found: usize },
+}
+
+fn describe_path(path: &[usize]) -> String
+{
+    std::iter::once("root".to_string()).chain(path.iter().map(|i| i.to_string())).join(".")
+}
+
+impl std::fmt::Display for EvalError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            EvalError::Overflow { path, operator, number } =>
+                write!(f, "{} packet at {} overflows a {}", operator.name(), describe_path(path), number),
+            EvalError::LiteralOverflow { path, number } =>
+                write!(f, "literal packet at {} does not fit in a {}", describe_path(path), number),
+            EvalError::Arity { path, operator, found } =>
+                write!(f, "{} packet at {} has {} sub-packets", operator.name(), describe_path(path), found),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprError
+{
+    UnexpectedEnd,
+    Unexpected { position: usize, token: String },
+    UnknownOperator { position: usize, name: String },
+    InvalidNumber { position: usize, token: String },
+    Arity { position: usize, operator: Operator, found: usize },
+}
+
+impl std::fmt::Display for ExprError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            ExprError::UnexpectedEnd => write!(f, "expression ends early"),
+            ExprError::Unexpected { position, token } => write!(f, "unexpected {:?} at position {}", token, position),
+            ExprError::UnknownOperator { position, name } => write!(f, "unknown operator {:?} at position {}", name, position),
+            ExprError::InvalidNumber { position, token } => write!(f, "{:?} at position {} is not a number", token, position),
+            ExprError::Arity { position, operator, found } =>
+                write!(f, "{} at position {} has {} operands", operator.name(), position, found),
+        }
+    }
+}
+
+//Tokens are parentheses and runs of anything else, each with its byte position
+fn tokenize(expr: &str) -> Vec<(usize, &str)>
+{
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (i, c) in expr.char_indices()
+    {
+        if c.is_whitespace() || c == '(' || c == ')'
+        {
+            if let Some(s) = start.take()
+            {
+                tokens.push((s, &expr[s..i]));
+            }
+            if !c.is_whitespace()
+            {
+                tokens.push((i, &expr[i..i + 1]));
+            }
+        }
+        else if start.is_none()
+        {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start
+    {
+        tokens.push((s, &expr[s..]));
+    }
+    tokens
+}
+
+//Reads a value or name with an optional @version suffix; versions default to 0
+fn split_version(position: usize, token: &str) -> Result<(&str, u64), ExprError>
+{
+    match token.split_once('@')
+    {
+        None => Ok((token, 0)),
+        Some((name, version)) => version.parse::<u64>()
+            .map(|version| (name, version))
+            .map_err(|_| ExprError::InvalidNumber { position, token: token.to_string() })
+    }
+}
+
+type Tokens<'a> = std::iter::Peekable<std::vec::IntoIter<(usize, &'a str)>>;
+
+fn parse_expr(tokens: &mut Tokens) -> Result<Packet, ExprError>
+{
+    let (position, token) = tokens.next().ok_or(ExprError::UnexpectedEnd)?;
+    if token == ")"
+    {
+        return Err(ExprError::Unexpected { position, token: token.to_string() });
+    }
+    if token != "("
+    {
+        let (value, version) = split_version(position, token)?;
+        let value = value.parse::<BigUint>().map_err(|_| ExprError::InvalidNumber { position, token: token.to_string() })?;
+        return Ok(Packet { version, data: PacketData::Literal(value) });
+    }
+
+    let (name_position, name) = tokens.next().ok_or(ExprError::UnexpectedEnd)?;
+    let (name, version) = split_version(name_position, name)?;
+    let operator = Operator::from_name(name)
+        .ok_or_else(|| ExprError::UnknownOperator { position: name_position, name: name.to_string() })?;
 
-        match self.type_id
+    let mut subpackets = Vec::new();
+    loop
+    {
+        match tokens.peek()
         {
-            4 => match self.data
+            None => return Err(ExprError::UnexpectedEnd),
+            Some(&(_, ")")) => { tokens.next(); break; }
+            Some(_) => subpackets.push(parse_expr(tokens)?)
+        }
+    }
+    if !operator.accepts(subpackets.len())
+    {
+        return Err(ExprError::Arity { position, operator, found: subpackets.len() });
+    }
+    Ok(Packet { version, data: PacketData::Operator(operator, subpackets) })
+}
+
+//Builds a packet tree from its S-expression form, as written by to_sexpr
+pub fn parse_sexpr(expr: &str) -> Result<Packet, ExprError>
+{
+    let mut tokens = tokenize(expr).into_iter().peekable();
+    let packet = parse_expr(&mut tokens)?;
+    match tokens.next()
+    {
+        None => Ok(packet),
+        Some((position, token)) => Err(ExprError::Unexpected { position, token: token.to_string() })
+    }
+}
+
+//Infix operators from loosest to tightest binding; comparisons take exactly two operands,
+//while a chain of + or * becomes one packet with every operand
+const PRECEDENCE : [&[Operator]; 3] = [
+    &[Operator::GreaterThan, Operator::LessThan, Operator::EqualTo],
+    &[Operator::Sum],
+    &[Operator::Product],
+];
+
+//Numbers, names, ==, and single-character symbols, each with its byte position
+fn tokenize_infix(expr: &str) -> Result<Vec<(usize, &str)>, ExprError>
+{
+    let mut tokens = Vec::new();
+    let mut chars = expr.char_indices().peekable();
+    while let Some((start, c)) = chars.next()
+    {
+        let mut end = start + c.len_utf8();
+        match c
+        {
+            _ if c.is_whitespace() => continue,
+            _ if c.is_ascii_alphanumeric() =>
             {
-                PacketData::Literal(value) => value,
-                _ => unreachable!("parse error")
-            },
-            0 => values.into_iter().sum(),
-            1 => values.into_iter().product(),
-            2 => values.into_iter().min().unwrap(),
-            3 => values.into_iter().max().unwrap(),
-            5 => (values[0] > values[1]) as u64,
-            6 => (values[0] < values[1]) as u64,
-            7 => (values[0] == values[1]) as u64,
-            _ => unreachable!("invalid input")
+                while let Some(&(i, c)) = chars.peek().filter(|(_, c)| c.is_ascii_alphanumeric())
+                {
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+            }
+            '=' if chars.peek().map(|&(_, c)| c) == Some('=') => { chars.next(); end += 1; }
+            '(' | ')' | ',' | '+' | '*' | '<' | '>' => {}
+            _ => return Err(ExprError::Unexpected { position: start, token: c.to_string() })
         }
+        tokens.push((start, &expr[start..end]));
     }
+    Ok(tokens)
+}
+
+struct InfixParser<'a>
+{
+    tokens: Vec<(usize, &'a str)>,
+    pos: usize,
 }
 
-fn parse(input: &str) -> Vec<Packet>
+impl InfixParser<'_>
 {
-    let mut stream = BitStream::from_hex(input);
+    fn peek(&self) -> Option<(usize, &str)>
+    {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<(usize, &str), ExprError>
+    {
+        let token = self.tokens.get(self.pos).copied().ok_or(ExprError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), ExprError>
+    {
+        match self.next()?
+        {
+            (_, token) if token == expected => Ok(()),
+            (position, token) => Err(ExprError::Unexpected { position, token: token.to_string() })
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Packet, ExprError>
+    {
+        let Some(operators) = PRECEDENCE.get(level) else { return self.atom(); };
+        let first = self.binary(level + 1)?;
+
+        let mut operator : Option<(usize, Operator)> = None;
+        let mut operands = vec![first];
+        while let Some((position, token)) = self.peek()
+        {
+            let Some(next) = Operator::from_symbol(token).filter(|op| operators.contains(op)) else { break; };
+            if operator.is_some_and(|(_, op)| op != next)
+            {
+                return Err(ExprError::Unexpected { position, token: token.to_string() });
+            }
+            operator.get_or_insert((position, next));
+            self.pos += 1;
+            operands.push(self.binary(level + 1)?);
+        }
+
+        match operator
+        {
+            None => Ok(operands.pop().unwrap()),
+            Some((position, operator)) if !operator.accepts(operands.len()) =>
+                Err(ExprError::Arity { position, operator, found: operands.len() }),
+            Some((_, operator)) => Ok(Packet { version: 0, data: PacketData::Operator(operator, operands) })
+        }
+    }
+
+    //A number, a parenthesised expression, or a call such as max(3, 7)
+    fn atom(&mut self) -> Result<Packet, ExprError>
+    {
+        let (position, token) = self.next()?;
+        if token == "("
+        {
+            let packet = self.binary(0)?;
+            self.expect(")")?;
+            return Ok(packet);
+        }
+        if token.starts_with(|c: char| c.is_ascii_digit())
+        {
+            let value = token.parse::<BigUint>().map_err(|_| ExprError::InvalidNumber { position, token: token.to_string() })?;
+            return Ok(Packet { version: 0, data: PacketData::Literal(value) });
+        }
+
+        let operator = match token.starts_with(|c: char| c.is_ascii_alphabetic())
+        {
+            true => Operator::from_name(token).ok_or_else(|| ExprError::UnknownOperator { position, name: token.to_string() })?,
+            false => return Err(ExprError::Unexpected { position, token: token.to_string() })
+        };
+        self.expect("(")?;
+        let mut operands = Vec::new();
+        if self.peek().map(|(_, token)| token) != Some(")")
+        {
+            operands.push(self.binary(0)?);
+            while self.peek().map(|(_, token)| token) == Some(",")
+            {
+                self.pos += 1;
+                operands.push(self.binary(0)?);
+            }
+        }
+        self.expect(")")?;
+        match operator.accepts(operands.len())
+        {
+            true => Ok(Packet { version: 0, data: PacketData::Operator(operator, operands) }),
+            false => Err(ExprError::Arity { position, operator, found: operands.len() })
+        }
+    }
+}
+
+//Builds a packet tree from its arithmetic form, as written by to_infix
+pub fn parse_infix(expr: &str) -> Result<Packet, ExprError>
+{
+    let mut parser = InfixParser { tokens: tokenize_infix(expr)?, pos: 0 };
+    let packet = parser.binary(0)?;
+    match parser.peek()
+    {
+        None => Ok(packet),
+        Some((position, token)) => Err(ExprError::Unexpected { position, token: token.to_string() })
+    }
+}
+
+pub fn decode(input: &str) -> Result<Vec<Packet>, BitsError>
+{
+    parse(input)
+}
+
+fn parse(input: &str) -> Result<Vec<Packet>, BitsError>
+{
+    let input = input.trim();
+    let bytes = decode_hex(input)?;
+    let mut stream = BitStream::new(&bytes, input.len() * 4);
     stream.parse()
 }
 
+//Each packet in the transmission as an S-expression with versions, then as arithmetic
+pub fn pretty(input: &str) -> String
+{
+    match parse(input)
+    {
+        Ok(packets) => packets.iter().map(|p| format!("{}\n{}", p.to_sexpr(true), p.to_infix())).join("\n"),
+        Err(e) => e.to_string()
+    }
+}
+
+//Encodes an S-expression as a hex transmission
+pub fn compile(expr: &str) -> String
+{
+    compile_with(expr, parse_sexpr)
+}
+
+//Encodes an arithmetic expression as a hex transmission
+pub fn compile_infix(expr: &str) -> String
+{
+    compile_with(expr, parse_infix)
+}
+
+fn compile_with(expr: &str, parse: fn(&str) -> Result<Packet, ExprError>) -> String
+{
+    match parse(expr)
+    {
+        Ok(packet) => encode(&packet, &EncodePolicy::default()).unwrap_or_else(|e| e.to_string()),
+        Err(e) => e.to_string()
+    }
+}
+
 pub fn solution_a(input: &str) -> String
 {
-    let mut packets = parse(input);
+    let mut packets = match parse(input)
+    {
+        Ok(packets) => packets,
+        Err(e) => return e.to_string()
+    };
     let mut version_sum = 0;
     while !packets.is_empty()
     {
@@ -158,7 +817,7 @@ pub fn solution_a(input: &str) -> String
         {
             match p.data
             {
-                PacketData::Subpackets(subpackets) => { Some(subpackets) }
+                PacketData::Operator(_, subpackets) => { Some(subpackets) }
                 _ => None
             }
         }).flatten().collect();
@@ -168,6 +827,292 @@ pub fn solution_a(input: &str) -> String
 
 pub fn solution_b(input: &str) -> String
 {
-    let packets = parse(input);
-    packets[0].evaluate().to_string()
-}
\ No newline at end of file
+    match parse(input)
+    {
+        Ok(packets) => packets[0].evaluate::<u64>().map_or_else(|e| e.to_string(), |value| value.to_string()),
+        Err(e) => e.to_string()
+    }
+}
+
+//The outermost packet evaluated at each width, from checked u64 up to unbounded
+pub fn evaluate_widths(input: &str) -> String
+{
+    fn show<N: Number>(packet: &Packet) -> String
+    {
+        let result = packet.evaluate::<N>().map_or_else(|e| e.to_string(), |value| value.to_string());
+        format!("{}: {}", N::NAME, result)
+    }
+
+    match parse(input)
+    {
+        Ok(packets) => [show::<u64>(&packets[0]), show::<u128>(&packets[0]), show::<BigUint>(&packets[0])].join("\n"),
+        Err(e) => e.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthPolicy
+{
+    BitLength,
+    PacketCount,
+    //Bit length when it fits in 15 bits, otherwise the packet count
+    Auto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodePolicy
+{
+    pub length: LengthPolicy,
+    //Literals use at least this many 4-bit groups, padding with leading zero groups
+    pub min_literal_groups: usize,
+}
+
+impl Default for EncodePolicy
+{
+    fn default() -> Self
+    {
+        EncodePolicy { length: LengthPolicy::Auto, min_literal_groups: 1 }
+    }
+}
+
+struct BitWriter
+{
+    bits : Vec<bool>
+}
+
+impl BitWriter
+{
+    fn write(&mut self, field: &'static str, value: u64, num_bits: usize) -> Result<(), BitsError>
+    {
+        if num_bits < 64 && value >> num_bits != 0
+        {
+            return Err(BitsError::FieldOverflow { field, bits: num_bits, value });
+        }
+        self.bits.extend((0..num_bits).rev().map(|i| (value >> i) & 1 == 1));
+        Ok(())
+    }
+
+    fn write_packet(&mut self, packet: &Packet, policy: &EncodePolicy) -> Result<(), BitsError>
+    {
+        self.write("version", packet.version, 3)?;
+        self.write("type id", packet.type_id(), 3)?;
+        match &packet.data
+        {
+            PacketData::Literal(value) =>
+            {
+                let groups = value.bits().div_ceil(4).max(policy.min_literal_groups).max(1);
+                for group in (0..groups).rev()
+                {
+                    self.write("continue bit", (group > 0) as u64, 1)?;
+                    self.write("literal group", value.nibble(group) as u64, 4)?;
+                }
+            }
+            PacketData::Operator(operator, subpackets) =>
+            {
+                //The decoder would reject this, or for no sub-packets silently drop the packet
+                if !operator.accepts(subpackets.len())
+                {
+                    return Err(BitsError::OperandCount { type_id: operator.type_id(), found: subpackets.len() });
+                }
+
+                let mut body = BitWriter { bits: Vec::new() };
+                for subpacket in subpackets.iter()
+                {
+                    body.write_packet(subpacket, policy)?;
+                }
+
+                let use_bit_length = match policy.length
+                {
+                    LengthPolicy::BitLength => true,
+                    LengthPolicy::PacketCount => false,
+                    LengthPolicy::Auto => body.bits.len() < 1 << 15
+                };
+                match use_bit_length
+                {
+                    true =>
+                    {
+                        self.write("length type", 0, 1)?;
+                        self.write("sub-packet bit length", body.bits.len() as u64, 15)?;
+                    }
+                    false =>
+                    {
+                        self.write("length type", 1, 1)?;
+                        self.write("sub-packet count", subpackets.len() as u64, 11)?;
+                    }
+                }
+                self.bits.extend(body.bits);
+            }
+        }
+        Ok(())
+    }
+
+    //Pads with zeros to a whole number of hex digits
+    fn to_hex(&self) -> String
+    {
+        self.bits.chunks(4)
+            .map(|chunk|
+            {
+                let digit = (0..4).fold(0, |digit, i| digit << 1 | *chunk.get(i).unwrap_or(&false) as u32);
+                std::char::from_digit(digit, 16).unwrap().to_ascii_uppercase()
+            })
+            .collect()
+    }
+}
+
+pub fn encode(packet: &Packet, policy: &EncodePolicy) -> Result<String, BitsError>
+{
+    let mut writer = BitWriter { bits: Vec::new() };
+    writer.write_packet(packet, policy)?;
+    Ok(writer.to_hex())
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    const POLICIES : [EncodePolicy; 3] = [
+        EncodePolicy { length: LengthPolicy::Auto, min_literal_groups: 1 },
+        EncodePolicy { length: LengthPolicy::BitLength, min_literal_groups: 1 },
+        EncodePolicy { length: LengthPolicy::PacketCount, min_literal_groups: 3 },
+    ];
+
+    const EXAMPLES : [&str; 12] = [
+        "D2FE28", "38006F45291200", "EE00D40C823060",
+        "8A004A801A8002F478", "620080001611562C8802118E34", "C0015000016115A2E0802F182340", "A0016C880162017C3686B18A3D4780",
+        "C200B40A82", "04005AC33890", "880086C3E88112", "9C005AC2F8F0", "9C0141080250320F1802104A08",
+    ];
+
+    //xorshift64, enough to vary the generated trees
+    struct Rng(u64);
+
+    impl Rng
+    {
+        fn next(&mut self) -> u64
+        {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: u64) -> u64
+        {
+            self.next() % n
+        }
+    }
+
+    fn generate(rng: &mut Rng, depth: usize) -> Packet
+    {
+        let version = rng.below(8);
+        if depth == 0 || rng.below(3) == 0
+        {
+            //Now and then wider than any fixed-size integer
+            let wide = BigUint::from(u64::MAX);
+            let value = (0..rng.below(4).saturating_sub(1))
+                .fold(BigUint::from(rng.next() >> rng.below(64)), |value, _| &(&value * &wide) + &BigUint::from(rng.next()));
+            return Packet { version, data: PacketData::Literal(value) };
+        }
+
+        let operator = OPERATORS[rng.below(OPERATORS.len() as u64) as usize];
+        let count = match operator.accepts(1)
+        {
+            true => 1 + rng.below(4) as usize,
+            false => 2
+        };
+        let subpackets = (0..count).map(|_| generate(rng, depth - 1)).collect();
+        Packet { version, data: PacketData::Operator(operator, subpackets) }
+    }
+
+    fn assert_round_trip(packet: &Packet)
+    {
+        for policy in POLICIES.iter()
+        {
+            let hex = encode(packet, policy).unwrap();
+            assert_eq!(decode(&hex), Ok(vec![packet.clone()]), "{:?} with {:?}", packet, policy);
+        }
+    }
+
+    #[test]
+    fn examples_round_trip()
+    {
+        for example in EXAMPLES
+        {
+            let packets = decode(example).unwrap();
+            assert_eq!(packets.len(), 1);
+            assert_round_trip(&packets[0]);
+        }
+    }
+
+    #[test]
+    fn generated_trees_round_trip()
+    {
+        let mut rng = Rng(0x2021_1216);
+        for _ in 0..200
+        {
+            assert_round_trip(&generate(&mut rng, 4));
+        }
+    }
+
+    fn without_versions(packet: &Packet) -> Packet
+    {
+        let data = match &packet.data
+        {
+            PacketData::Literal(value) => PacketData::Literal(value.clone()),
+            PacketData::Operator(operator, subpackets) => PacketData::Operator(*operator, subpackets.iter().map(without_versions).collect())
+        };
+        Packet { version: 0, data }
+    }
+
+    #[test]
+    fn expressions_read_back()
+    {
+        let mut rng = Rng(0x2021_1216);
+        for _ in 0..200
+        {
+            let packet = generate(&mut rng, 4);
+            assert_eq!(parse_sexpr(&packet.to_sexpr(true)), Ok(packet.clone()));
+            assert_eq!(parse_infix(&packet.to_infix()), Ok(without_versions(&packet)), "{}", packet.to_infix());
+        }
+    }
+
+    #[test]
+    fn infix_precedence()
+    {
+        assert_eq!(parse_infix("1 + 2 * 3 < 4"), parse_sexpr("(lt (sum 1 (product 2 3)) 4)"));
+        assert_eq!(parse_infix("(1 + 2) + max(3, 7)"), parse_sexpr("(sum (sum 1 2) (max 3 7))"));
+        assert!(matches!(parse_infix("1 < 2 < 3"), Err(ExprError::Arity { .. })));
+        assert!(matches!(parse_infix("1 + 2 *"), Err(ExprError::UnexpectedEnd)));
+        assert!(matches!(parse_infix("foo(1)"), Err(ExprError::UnknownOperator { .. })));
+    }
+
+    #[test]
+    fn wide_literals_reach_evaluation()
+    {
+        //2^70 and 2^130, past u64 and u128 respectively
+        let hex = compile("(sum 1180591620717411303424 (product 1 1361129467683753853853498429727072845824))");
+        let packet = &decode(&hex).unwrap()[0];
+        assert_eq!(packet.evaluate::<u64>(), Err(EvalError::LiteralOverflow { path: vec![0], number: "u64" }));
+        assert_eq!(packet.evaluate::<u128>(), Err(EvalError::LiteralOverflow { path: vec![1, 1], number: "u128" }));
+        assert_eq!(packet.evaluate::<BigUint>().map(|value| value.to_string()), Ok("1361129467683753855034090050444484149248".to_string()));
+    }
+
+    #[test]
+    fn encode_rejects_wrong_operand_counts()
+    {
+        let literal = Packet { version: 0, data: PacketData::Literal(BigUint::from(1u64)) };
+        let empty_sum = Packet { version: 0, data: PacketData::Operator(Operator::Sum, Vec::new()) };
+        let unary_less = Packet { version: 0, data: PacketData::Operator(Operator::LessThan, vec![literal]) };
+        assert_eq!(encode(&empty_sum, &EncodePolicy::default()), Err(BitsError::OperandCount { type_id: 0, found: 0 }));
+        assert_eq!(encode(&unary_less, &EncodePolicy::default()), Err(BitsError::OperandCount { type_id: 6, found: 1 }));
+    }
+
+    #[test]
+    fn transmissions_without_packets_are_truncated()
+    {
+        assert_eq!(parse(""), Err(BitsError::Truncated { at_bit: 0, needed: 3, available: 0 }));
+        assert_eq!(parse("0000"), Err(BitsError::Truncated { at_bit: 7, needed: 15, available: 9 }));
+        assert_eq!(solution_b(""), "truncated at bit 0: needed 3 bits but only 0 remain");
+        assert_eq!(evaluate_widths("00"), "truncated at bit 7: needed 15 bits but only 1 remain");
+    }
+}
@@ -1,25 +1,181 @@
+use std::{collections::HashMap, fs, path::Path};
+use itertools::Itertools;
+
+const SVG_CELL_SIZE : f64 = 10.0;
+const SVG_MAX_DIM : usize = 1000;
+
 pub fn solution_a(input: &str) -> String
 {
     let target_area = TargetArea::new(input);
-    let highest_y = ((target_area.y_min+1)*target_area.y_min)/2;
-    highest_y.to_string()
+    match target_area.launches()
+    {
+        Some(launches) => launches.iter().map(|launch| launch.max_height).max().map_or("no hits".to_string(), |h| h.to_string()),
+        None => "unbounded".to_string()
+    }
 }
 
 pub fn solution_b(input: &str) -> String
 {
     let target_area = TargetArea::new(input);
-    let mut unique_solutions = 0;
-    for x_vel in 1..=target_area.x_max
+    match target_area.launches()
     {
-        for y_vel in target_area.y_min..=-target_area.y_min
+        Some(launches) => launches.len().to_string(),
+        None => "unbounded".to_string()
+    }
+}
+
+pub fn launches(input: &str) -> String
+{
+    let target_area = TargetArea::new(input);
+    let ranges = target_area.velocity_ranges();
+    let y_max = ranges.y.1.map_or("unbounded".to_string(), |y| y.to_string());
+    let header = format!("x velocity {}..={}, y velocity {}..={}", ranges.x.0, ranges.x.1, ranges.y.0, y_max);
+    let Some(launches) = target_area.launches() else
+    {
+        return format!("{}\ninfinitely many velocities hit", header);
+    };
+
+    std::iter::once(header)
+        .chain(launches.iter().map(|launch| format!("{},{}: hits at step {} at {},{}, max height {}",
+            launch.velocity.0, launch.velocity.1, launch.hit_step, launch.landing.0, launch.landing.1, launch.max_height)))
+        .join("\n")
+}
+
+//Drawn like the puzzle's diagrams, with y up: S is the launcher, # the path, T the target,
+//and the last position X on a hit or M on a miss. With overlay, o marks every other valid path.
+pub fn plot(input: &str, velocity: (i32, i32), overlay: bool) -> String
+{
+    let target_area = TargetArea::new(input);
+    let trajectory = target_area.trajectory(velocity.0, velocity.1);
+    let others = match overlay
+    {
+        true => match target_area.valid_trajectories()
         {
-            if target_area.check_solution(x_vel, y_vel) { unique_solutions += 1}
-        }
+            Some(others) => others,
+            None => return "infinitely many valid trajectories to overlay".to_string()
+        },
+        false => Vec::new()
+    };
+
+    let mut cells : HashMap<(i64, i64), char> = HashMap::new();
+    for (x, y) in target_area.cells()
+    {
+        cells.insert((x, y), 'T');
+    }
+    for &position in others.iter().flat_map(|other| other.positions.iter())
+    {
+        cells.insert(position, 'o');
+    }
+    for &position in trajectory.positions.iter()
+    {
+        cells.insert(position, '#');
+    }
+    cells.insert((0, 0), 'S');
+    if let Some(&last) = trajectory.positions.last().filter(|_| trajectory.positions.len() > 1)
+    {
+        cells.insert(last, if trajectory.hit { 'X' } else { 'M' });
     }
-    unique_solutions.to_string()
+
+    let (x_min, x_max) = cells.keys().map(|p| p.0).minmax().into_option().unwrap();
+    let (y_min, y_max) = cells.keys().map(|p| p.1).minmax().into_option().unwrap();
+    (y_min..=y_max).rev().map(|y|
+    {
+        (x_min..=x_max).map(|x| cells.get(&(x, y)).copied().unwrap_or('.')).collect::<String>()
+    }).join("\n")
 }
 
-struct TargetArea
+//Writes the plot as an SVG scaled to fit SVG_MAX_DIM, and returns the file name
+pub fn export_plot(input: &str, velocity: (i32, i32), overlay: bool) -> String
+{
+    let target_area = TargetArea::new(input);
+    let trajectory = target_area.trajectory(velocity.0, velocity.1);
+    let others = match overlay
+    {
+        true => match target_area.valid_trajectories()
+        {
+            Some(others) => others,
+            None => return "infinitely many valid trajectories to overlay".to_string()
+        },
+        false => Vec::new()
+    };
+
+    let corners = [(target_area.x_min as i64, target_area.y_min as i64), (target_area.x_max as i64, target_area.y_max as i64)];
+    let points = || trajectory.positions.iter().chain(others.iter().flat_map(|other| other.positions.iter())).chain(corners.iter());
+    let (x_min, x_max) = points().map(|p| p.0).minmax().into_option().unwrap();
+    let (y_min, y_max) = points().map(|p| p.1).minmax().into_option().unwrap();
+
+    //Cells are at most SVG_CELL_SIZE across, smaller when the plot would not otherwise fit
+    let (cols, rows) = (x_max - x_min + 1, y_max - y_min + 1);
+    let scale = (SVG_MAX_DIM as f64 / cols.max(rows) as f64).min(SVG_CELL_SIZE);
+    let (width, height) = (cols as f64 * scale, rows as f64 * scale);
+    let to_svg = |(x, y): (i64, i64)| ((x - x_min) as f64 * scale + scale / 2.0, (y_max - y) as f64 * scale + scale / 2.0);
+    let polyline = |trajectory: &Trajectory, style: &str|
+    {
+        let points = trajectory.positions.iter().map(|&p| { let (x, y) = to_svg(p); format!("{:.1},{:.1}", x, y) }).join(" ");
+        format!("<polyline points=\"{}\" fill=\"none\" {}/>", points, style)
+    };
+
+    let mut svg = vec![
+        format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\">", width, height),
+        format!("<rect width=\"{:.1}\" height=\"{:.1}\" fill=\"white\"/>", width, height),
+    ];
+    let (left, top) = to_svg((target_area.x_min as i64, target_area.y_max as i64));
+    svg.push(format!("<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"lightblue\" stroke=\"blue\"/>",
+        left - scale / 2.0, top - scale / 2.0,
+        (target_area.x_max - target_area.x_min + 1) as f64 * scale, (target_area.y_max - target_area.y_min + 1) as f64 * scale));
+    svg.extend(others.iter().map(|other| polyline(other, "stroke=\"grey\" stroke-width=\"0.5\" opacity=\"0.5\"")));
+    svg.push(polyline(&trajectory, "stroke=\"black\" stroke-width=\"1.5\""));
+
+    let (x, y) = to_svg((0, 0));
+    svg.push(format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"4\" fill=\"black\"/>", x, y));
+    let (x, y) = to_svg(*trajectory.positions.last().unwrap());
+    svg.push(match trajectory.hit
+    {
+        true => format!("<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"5\" fill=\"green\"/>", x, y),
+        false => format!("<path d=\"M{:.1},{:.1} l10,10 m0,-10 l-10,10\" stroke=\"red\" stroke-width=\"2\"/>", x - 5.0, y - 5.0)
+    });
+    svg.push("</svg>".to_string());
+
+    let filename = format!("./output/17/trajectory_{}_{}.svg", velocity.0, velocity.1);
+    let path = Path::new(&filename);
+    fs::create_dir_all(path.parent().unwrap()).unwrap_or_else(|_| panic!("cannot create directory for {}", filename));
+    fs::write(path, svg.join("\n")).unwrap_or_else(|_| panic!("cannot write file at {}", filename));
+    filename
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Launch
+{
+    pub velocity: (i32, i32),
+    //The first step that ends inside the target, and where it ends
+    pub hit_step: u64,
+    pub landing: (i64, i64),
+    //The highest point reached up to and including the hit
+    pub max_height: i64,
+}
+
+//Inclusive bounds on the velocities that can possibly hit; the y range has no
+//upper bound when the target covers y=0, since every upward launch comes back down through it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VelocityRanges
+{
+    pub x: (i32, i32),
+    pub y: (i32, Option<i32>),
+}
+
+//Every position from the launcher on; the last is where the probe hit, or the first
+//point from which it could no longer reach the target
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trajectory
+{
+    pub positions: Vec<(i64, i64)>,
+    pub hit: bool,
+}
+
+//Inclusive ranges of steps; the end is None when the range never ends
+type Steps = (u64, Option<u64>);
+
+pub struct TargetArea
 {
     x_min: i32,
     x_max: i32,
@@ -29,9 +185,9 @@ struct TargetArea
 
 impl TargetArea
 {
-    fn new(input: &str) -> Self
+    pub fn new(input: &str) -> Self
     {
-        let data = input.trim_start_matches("target area: x=").split_once(", y=").unwrap();
+        let data = input.trim().trim_start_matches("target area: x=").split_once(", y=").unwrap();
         let x_data = data.0.split_once("..").unwrap();
         let y_data = data.1.split_once("..").unwrap();
         let x_min = x_data.0.parse::<i32>().unwrap();
@@ -41,28 +197,230 @@ impl TargetArea
         TargetArea { x_min, x_max, y_min, y_max }
     }
 
-    fn check_solution(&self, mut x_vel: i32, mut y_vel: i32) -> bool
+    fn check_solution(&self, x_vel: i32, y_vel: i32) -> bool
     {
-        let mut x = 0;
-        let mut y = 0;
-        while self.position_is_valid(x, y)
+        self.trajectory(x_vel, y_vel).hit
+    }
+
+    //Steps from the launcher until the probe ends a step inside the target or can no longer reach it
+    pub fn trajectory(&self, x_vel: i32, y_vel: i32) -> Trajectory
+    {
+        let (mut x, mut y) = (0, 0);
+        let (mut x_vel, mut y_vel) = (x_vel as i64, y_vel as i64);
+        let mut positions = vec![(x, y)];
+        while self.position_is_valid(x, y, x_vel, y_vel)
         {
-            if self.is_in_target_area(x, y) { return true; }
             x += x_vel;
             y += y_vel;
-            if x_vel > 0 { x_vel -= 1 }
-            y_vel -= 1;            
+            x_vel -= x_vel.signum();
+            y_vel -= 1;
+            positions.push((x, y));
+            if self.is_in_target_area(x, y)
+            {
+                return Trajectory { positions, hit: true };
+            }
+        }
+        Trajectory { positions, hit: false }
+    }
+
+    //Whether the probe, at this position and about to move at this velocity, can still reach the target
+    fn position_is_valid(&self, x: i64, y: i64, x_vel: i64, y_vel: i64) -> bool
+    {
+        let (x_min, x_max, y_min) = (self.x_min as i64, self.x_max as i64, self.y_min as i64);
+        let falling_below = y < y_min && y_vel <= 0;
+        let past_x = match x_vel.signum()
+        {
+            1 => x > x_max,
+            -1 => x < x_min,
+            _ => x < x_min || x > x_max
+        };
+        !falling_below && !past_x
+    }
+
+    fn is_in_target_area(&self, x: i64, y: i64) -> bool
+    {
+        self.x_min as i64 <= x && x <= self.x_max as i64 && self.y_min as i64 <= y && y <= self.y_max as i64
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (i64, i64)>
+    {
+        (self.x_min as i64..=self.x_max as i64).cartesian_product(self.y_min as i64..=self.y_max as i64)
+    }
+
+    fn valid_trajectories(&self) -> Option<Vec<Trajectory>>
+    {
+        let launches = self.launches()?;
+        Some(launches.iter().map(|launch| self.trajectory(launch.velocity.0, launch.velocity.1)).collect())
+    }
+
+    //The first step lands exactly one velocity away, and neither axis ever moves back
+    //past where it has been once it turns, which bounds both ranges
+    pub fn velocity_ranges(&self) -> VelocityRanges
+    {
+        let x = match (self.x_min, self.x_max)
+        {
+            (x_min, x_max) if x_min > 0 => (triangular_root(x_min as i64) as i32, x_max),
+            (x_min, x_max) if x_max < 0 => (x_min, -triangular_root(-x_max as i64) as i32),
+            (x_min, x_max) => (x_min, x_max)
+        };
+
+        //Launched upwards at v, the probe is back at y=0 after 2v+1 steps and moving at -(v+1)
+        let y = match (self.y_min, self.y_max)
+        {
+            (y_min, y_max) if y_min > 0 => (triangular_root(y_min as i64) as i32, Some(y_max)),
+            (y_min, y_max) if y_max < 0 => (y_min, Some(-y_min - 1)),
+            (y_min, _) => (y_min, None)
+        };
+        VelocityRanges { x, y }
+    }
+
+    //Every velocity that hits, ordered by x then y velocity, or None if infinitely many do
+    pub fn launches(&self) -> Option<Vec<Launch>>
+    {
+        let ranges = self.velocity_ranges();
+        let x_steps : Vec<(i32, Steps)> = (ranges.x.0..=ranges.x.1)
+            .filter_map(|x_vel| self.x_steps(x_vel).map(|steps| (x_vel, steps)))
+            .collect();
+
+        //Without a y bound, the target covers y=0: if some x velocity stays inside forever
+        //then every high enough launch hits, otherwise hits must happen by the last x step
+        let y_upper = match ranges.y.1
+        {
+            Some(y_upper) => y_upper as i64,
+            None =>
+            {
+                let last_step = x_steps.iter().try_fold(0, |max, (_, (_, last))| last.map(|last| max.max(last)))?;
+                self.y_max as i64 + last_step as i64
+            }
+        };
+
+        let launches = x_steps.iter()
+            .cartesian_product(ranges.y.0 as i64..=y_upper)
+            .filter_map(|(&(x_vel, x_steps), y_vel)| self.launch(x_vel, y_vel, x_steps))
+            .collect();
+        Some(launches)
+    }
+
+    fn launch(&self, x_vel: i32, y_vel: i64, x_steps: Steps) -> Option<Launch>
+    {
+        let hit_step = self.y_steps(y_vel).into_iter()
+            .flatten()
+            .filter_map(|y_steps| intersect(x_steps, y_steps))
+            .min()?;
+        let landing = (x_position(x_vel as i64, hit_step), y_position(y_vel, hit_step));
+        let max_height = y_position(y_vel, hit_step.min(y_vel.max(0) as u64));
+        Some(Launch { velocity: (x_vel, y_vel as i32), hit_step, landing, max_height })
+    }
+
+    //|x| only grows, so the steps ending inside the target's columns are a single range
+    fn x_steps(&self, x_vel: i32) -> Option<Steps>
+    {
+        let (x_vel, sign) = (x_vel.unsigned_abs() as i64, x_vel.signum() as i64);
+        let (lo, hi) = match sign
+        {
+            -1 => (-self.x_max as i64, -self.x_min as i64),
+            _ => (self.x_min as i64, self.x_max as i64)
+        };
+        let distance = |step: u64| x_position(x_vel, step);
+        let resting = distance(x_vel as u64);
+        if resting < lo || distance(1) > hi
+        {
+            return None;
+        }
+
+        let first = first_step(1, x_vel.max(1) as u64, |step| distance(step) >= lo);
+        match resting <= hi
+        {
+            true => Some((first, None)),
+            false =>
+            {
+                //Steps can jump clean over narrow targets
+                let last = first_step(first, x_vel as u64, |step| distance(step) > hi) - 1;
+                (first <= last).then_some((first, Some(last)))
+            }
         }
-        false
     }
 
-    fn position_is_valid(&self, x: i32, y: i32) -> bool
+    //y rises until step y_vel and falls after, so the steps ending inside the target's
+    //rows are at most one range on the way up and one on the way down
+    fn y_steps(&self, y_vel: i64) -> [Option<Steps>; 2]
     {
-        x <= self.x_max && y >= self.y_min
+        let (lo, hi) = (self.y_min as i64, self.y_max as i64);
+        let height = |step: u64| y_position(y_vel, step);
+        let peak = y_vel.max(0) as u64;
+
+        let rising = match peak >= 1 && height(peak) >= lo && height(1) <= hi
+        {
+            true =>
+            {
+                let first = first_step(1, peak, |step| height(step) >= lo);
+                let last = first_step(first, peak + 1, |step| step > peak || height(step) > hi) - 1;
+                (first <= last).then_some((first, Some(last)))
+            }
+            false => None
+        };
+
+        //Double until the probe is below the target, then search the falling side for the band
+        let mut below = peak + 1;
+        while height(below) >= lo
+        {
+            below *= 2;
+        }
+        let first = first_step(peak + 1, below, |step| height(step) <= hi);
+        let last = first_step(first, below, |step| height(step) < lo) - 1;
+        let falling = (first <= last).then_some((first, Some(last)));
+
+        [rising, falling]
     }
+}
+
+//Smallest v with v(v+1)/2 >= n, for n > 0
+fn triangular_root(n: i64) -> i64
+{
+    let mut v = (((8 * n + 1) as f64).sqrt() as i64 - 1) / 2;
+    while v * (v + 1) / 2 < n { v += 1; }
+    while v > 0 && (v - 1) * v / 2 >= n { v -= 1; }
+    v
+}
 
-    fn is_in_target_area(&self, x: i32, y: i32) -> bool
+//Drag takes one off the speed each step until it reaches zero
+fn x_position(x_vel: i64, step: u64) -> i64
+{
+    let (speed, step) = (x_vel.abs(), step as i64);
+    let distance = match step <= speed
+    {
+        true => step * speed - step * (step - 1) / 2,
+        false => speed * (speed + 1) / 2
+    };
+    x_vel.signum() * distance
+}
+
+fn y_position(y_vel: i64, step: u64) -> i64
+{
+    let step = step as i64;
+    step * y_vel - step * (step - 1) / 2
+}
+
+//The first step in [lo, hi] where a monotone condition holds, or hi + 1 if it never does
+fn first_step(mut lo: u64, mut hi: u64, condition: impl Fn(u64) -> bool) -> u64
+{
+    hi += 1;
+    while lo < hi
     {
-        self.x_min <= x && x <= self.x_max && self.y_min <= y && y <= self.y_max
+        let mid = lo + (hi - lo) / 2;
+        match condition(mid)
+        {
+            true => hi = mid,
+            false => lo = mid + 1
+        }
     }
-}
\ No newline at end of file
+    lo
+}
+
+//The first step in both ranges
+fn intersect(a: Steps, b: Steps) -> Option<u64>
+{
+    let first = a.0.max(b.0);
+    let within = |steps: Steps| steps.1.is_none_or(|last| first <= last);
+    (within(a) && within(b)).then_some(first)
+}
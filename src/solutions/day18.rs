@@ -1,12 +1,12 @@
 pub fn solution_a(input: &str) -> String
 {
     let numbers = parse(input);
-    let mut number = numbers[0].clone();
-    for n in numbers.iter().skip(1)
+    let Some(number) = numbers.into_iter().sum::<Option<SnailfishNumber>>() else { return "no snailfish numbers".to_string(); };
+    match number.magnitude()
     {
-        number = number.add(n);
-    }    
-    number.magnitude().to_string()
+        Some(magnitude) => magnitude.to_string(),
+        None => "magnitude does not fit in 64 bits".to_string()
+    }
 }
 
 pub fn solution_b(input: &str) -> String
@@ -19,53 +19,214 @@ pub fn solution_b(input: &str) -> String
         {
             if std::ptr::eq(number, other) { continue; }
             
-            let result = number.clone().add(other);
-            let magnitude = result.magnitude();
+            //Sums are reduced, so every regular number is below 10 and the magnitude is small
+            let result = number + other;
+            let magnitude = result.magnitude().unwrap();
             if magnitude > largest { largest = magnitude; }
         }
     }
     largest.to_string()
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnailfishError
+{
+    UnexpectedChar { position: usize, found: char, expected: &'static str },
+    UnexpectedEnd { expected: &'static str },
+    TooLarge { position: usize },
+}
+
+impl std::fmt::Display for SnailfishError
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        match self
+        {
+            SnailfishError::UnexpectedChar { position, found, expected } =>
+                write!(f, "expected {} at position {} but found {:?}", expected, position, found),
+            SnailfishError::UnexpectedEnd { expected } => write!(f, "expected {} but the number ended", expected),
+            SnailfishError::TooLarge { position } => write!(f, "regular number at position {} is larger than {}", position, MAX_REGULAR),
+        }
+    }
+}
+
+//Reduction splits and explodes its way down roughly once per unit of value, so parsing
+//stops at a size where adding still finishes quickly
+const MAX_REGULAR : u64 = 1_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct SnailfishElement
 {
-    value: u32,
+    //Splits keep the total of all values and explodes keep or lower it, so no value in a sum
+    //of n regular numbers exceeds n * MAX_REGULAR
+    value: u64,
     depth: u32
 }
 
-#[derive(Clone)]
-struct SnailfishNumber
+//The regular numbers in order, each with the number of pairs around it less one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnailfishNumber
 {
     elements: Vec<SnailfishElement>
 }
 
-impl SnailfishNumber
+//Recursive descent over pair := '[' element ',' element ']' and element := digits | pair
+struct Parser<'a>
 {
-    fn new() -> Self
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    elements: Vec<SnailfishElement>
+}
+
+impl Parser<'_>
+{
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), SnailfishError>
+    {
+        match self.chars.next()
+        {
+            Some((_, found)) if found == c => Ok(()),
+            Some((position, found)) => Err(SnailfishError::UnexpectedChar { position, found, expected }),
+            None => Err(SnailfishError::UnexpectedEnd { expected })
+        }
+    }
+
+    fn pair(&mut self, depth: u32) -> Result<(), SnailfishError>
     {
-        Self { elements : Vec::new() }
+        self.expect('[', "'['")?;
+        self.element(depth)?;
+        self.expect(',', "','")?;
+        self.element(depth)?;
+        self.expect(']', "']'")
     }
 
-    fn parse(line: &str) -> Self
+    fn element(&mut self, depth: u32) -> Result<(), SnailfishError>
     {
-        let mut elements = Vec::new();
-        let mut depth = 0;
-        for c in line.chars()
+        let expected = "a regular number or '['";
+        match self.chars.peek().copied()
         {
-            match c
+            Some((_, '[')) => self.pair(depth + 1),
+            Some((position, c)) if c.is_ascii_digit() =>
             {
-                '[' => { depth += 1 },
-                ']' => { depth -= 1 },
-                ',' => { },
-                _ =>
+                let mut value = 0u64;
+                while let Some(digit) = self.chars.peek().and_then(|&(_, c)| c.to_digit(10))
                 {
-                    let value = c.to_digit(10).unwrap();
-                    elements.push(SnailfishElement { value, depth: depth-1 });
+                    value = value * 10 + digit as u64;
+                    if value > MAX_REGULAR
+                    {
+                        return Err(SnailfishError::TooLarge { position });
+                    }
+                    self.chars.next();
                 }
+                self.elements.push(SnailfishElement { value, depth });
+                Ok(())
+            }
+            Some((position, found)) => Err(SnailfishError::UnexpectedChar { position, found, expected }),
+            None => Err(SnailfishError::UnexpectedEnd { expected })
+        }
+    }
+}
+
+impl std::str::FromStr for SnailfishNumber
+{
+    type Err = SnailfishError;
+
+    fn from_str(line: &str) -> Result<Self, SnailfishError>
+    {
+        let mut parser = Parser { chars: line.trim().char_indices().peekable(), elements: Vec::new() };
+        parser.pair(0)?;
+        match parser.chars.next()
+        {
+            Some((position, found)) => Err(SnailfishError::UnexpectedChar { position, found, expected: "the end of the number" }),
+            None => Ok(SnailfishNumber { elements: parser.elements })
+        }
+    }
+}
+
+impl std::fmt::Display for SnailfishNumber
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result
+    {
+        self.write_pair(f, &mut 0, 0)
+    }
+}
+
+impl std::ops::Add<&SnailfishNumber> for &SnailfishNumber
+{
+    type Output = SnailfishNumber;
+
+    fn add(self, other: &SnailfishNumber) -> SnailfishNumber
+    {
+        self.clone() + other.clone()
+    }
+}
+
+impl std::ops::Add<&SnailfishNumber> for SnailfishNumber
+{
+    type Output = SnailfishNumber;
+
+    fn add(self, other: &SnailfishNumber) -> SnailfishNumber
+    {
+        self + other.clone()
+    }
+}
+
+impl std::ops::Add for SnailfishNumber
+{
+    type Output = SnailfishNumber;
+
+    fn add(mut self, other: SnailfishNumber) -> SnailfishNumber
+    {
+        self.elements.extend(other.elements);
+        for element in self.elements.iter_mut() { element.depth += 1; }
+        self.reduce()
+    }
+}
+
+//There is no empty snailfish number, so a sum of no numbers is None
+impl std::iter::Sum<SnailfishNumber> for Option<SnailfishNumber>
+{
+    fn sum<I: Iterator<Item = SnailfishNumber>>(iter: I) -> Self
+    {
+        iter.reduce(|sum, number| sum + number)
+    }
+}
+
+impl<'a> std::iter::Sum<&'a SnailfishNumber> for Option<SnailfishNumber>
+{
+    fn sum<I: Iterator<Item = &'a SnailfishNumber>>(iter: I) -> Self
+    {
+        iter.fold(None, |sum, number| Some(match sum
+        {
+            Some(sum) => sum + number,
+            None => number.clone()
+        }))
+    }
+}
+
+impl SnailfishNumber
+{
+    //Consumes elements from index i for the pair whose regular numbers sit at depth
+    fn write_pair(&self, f: &mut std::fmt::Formatter, i: &mut usize, depth: u32) -> std::fmt::Result
+    {
+        write!(f, "[")?;
+        self.write_element(f, i, depth)?;
+        write!(f, ",")?;
+        self.write_element(f, i, depth)?;
+        write!(f, "]")
+    }
+
+    //A deeper next element means this side is itself a pair
+    fn write_element(&self, f: &mut std::fmt::Formatter, i: &mut usize, depth: u32) -> std::fmt::Result
+    {
+        let element = &self.elements[*i];
+        match element.depth > depth
+        {
+            true => self.write_pair(f, i, depth + 1),
+            false =>
+            {
+                *i += 1;
+                write!(f, "{}", element.value)
             }
         }
-        SnailfishNumber { elements }
     }
 
     fn reduce(mut self) -> Self
@@ -80,7 +241,11 @@ impl SnailfishNumber
 
     fn explode(&mut self) -> bool
     {
-        let Some(i) = (0..self.elements.len()).find(|i| self.elements[*i].depth == 4) else { return false; };
+        //Parsed numbers can nest deeper than four, so look for the leftmost pair of regular numbers
+        //at depth 4 or more. Two adjacent regular numbers at the same depth are always a pair when
+        //no such adjacent pair comes before them.
+        let Some(i) = (0..self.elements.len().saturating_sub(1))
+            .find(|&i| self.elements[i].depth >= 4 && self.elements[i].depth == self.elements[i+1].depth) else { return false; };
 
         //Add left to left neighbor
         if i > 0 { self.elements[i-1].value += self.elements[i].value }
@@ -111,28 +276,103 @@ impl SnailfishNumber
         true
     }
 
-    fn add(mut self, other: &SnailfishNumber) -> Self
-    {
-        self.elements.extend(other.elements.clone());
-        for i in 0..self.elements.len() { self.elements[i].depth += 1; }
-        self.reduce()
-    }
-
-    fn magnitude(&self) -> u32
+    //None when the magnitude does not fit in 64 bits, which only deeply nested unreduced numbers reach
+    pub fn magnitude(&self) -> Option<u64>
     {
         let mut elements = self.elements.clone();
         while elements.len() > 1
         {
             let i = (0..elements.len()-1).find(|i| elements[*i].depth == elements[*i+1].depth).unwrap();
-            elements[i].value = 3*elements[i].value + 2*elements[i+1].value;
+            elements[i].value = elements[i].value.checked_mul(3)?
+                .checked_add(elements[i+1].value.checked_mul(2)?)?;
             if elements[i].depth > 0 { elements[i].depth -= 1; }
             elements.remove(i+1);
         }
-        elements[0].value
+        elements.first().map(|element| element.value)
     }
 }
 
 fn parse(input: &str) -> Vec<SnailfishNumber>
 {
-    input.lines().map(SnailfishNumber::parse).collect()
-}
\ No newline at end of file
+    input.lines()
+        .map(|line| line.parse().unwrap_or_else(|e| panic!("{}", e)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    fn number(s: &str) -> SnailfishNumber
+    {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn explodes_pairs_nested_deeper_than_four()
+    {
+        let sum = number("[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]") + number("[1,1]");
+        assert_eq!(sum.to_string(), "[[[[5,5],[5,0]],[[6,6],9]],[1,1]]");
+        assert_eq!(sum.magnitude(), Some(1603));
+
+        let deep = number("[[[[[[[1,2],3],4],5],6],7],[8,9]]") + number("[0,0]");
+        assert_eq!(deep.to_string(), "[[[[0,5],[8,9]],[8,9]],[0,0]]");
+        assert_eq!(deep.magnitude(), Some(1278));
+    }
+
+    #[test]
+    fn reduces_the_puzzle_example()
+    {
+        assert_eq!(number("[[[[4,3],4],4],[7,[[8,4],9]]]") + number("[1,1]"), number("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]"));
+    }
+
+    #[test]
+    fn sums_need_at_least_one_number()
+    {
+        let numbers : Vec<SnailfishNumber> = ["[1,1]", "[2,2]", "[3,3]", "[4,4]", "[5,5]", "[6,6]"].iter().map(|s| number(s)).collect();
+        assert_eq!(numbers.iter().sum::<Option<SnailfishNumber>>(), Some(number("[[[[5,0],[7,4]],[5,5]],[6,6]]")));
+        assert_eq!(numbers[..1].iter().sum::<Option<SnailfishNumber>>(), Some(number("[1,1]")));
+        assert_eq!(numbers[..0].iter().sum::<Option<SnailfishNumber>>(), None);
+        assert_eq!(Vec::<SnailfishNumber>::new().into_iter().sum::<Option<SnailfishNumber>>(), None);
+    }
+
+    #[test]
+    fn large_values_reduce_without_overflow()
+    {
+        let max = MAX_REGULAR;
+        let sum = number(&format!("[{},{}]", max, max)) + number("[1,1]");
+        assert_eq!(sum.to_string(), "[[[[7,7],[7,7]],[[7,7],[7,7]]],[[[0,7],[7,7]],[[8,8],[8,7]]]]");
+        assert_eq!(sum.magnitude(), Some(4081));
+
+        let mut exploding = number(&format!("[[[[{},[{},{}]],1],1],1]", max, max, max));
+        assert!(exploding.explode());
+        assert_eq!(exploding.elements[0].value, 2 * max);
+        assert_eq!(exploding.elements[2].value, max + 1);
+
+        let deep = (0..60).fold(format!("{}", max), |inner, _| format!("[{},0]", inner));
+        assert_eq!(number(&deep).magnitude(), None);
+        assert_eq!(number(&format!("[{},{}]", max, max)).magnitude(), Some(5 * max));
+    }
+
+    #[test]
+    fn rejects_regular_numbers_above_the_limit()
+    {
+        for value in [MAX_REGULAR + 1, u64::MAX]
+        {
+            let parsed = format!("[1,{}]", value).parse::<SnailfishNumber>();
+            assert_eq!(parsed, Err(SnailfishError::TooLarge { position: 3 }));
+        }
+        let digits = format!("[{}0,1]", u64::MAX);
+        assert_eq!(digits.parse::<SnailfishNumber>(), Err(SnailfishError::TooLarge { position: 1 }));
+    }
+
+    #[test]
+    fn display_and_parse_are_inverses()
+    {
+        for s in ["[1,2]", "[[1,2],3]", "[9,[8,7]]", "[[1,9],[8,5]]", "[[[[1,2],[3,4]],[[5,6],[7,8]]],9]", "[[123,[45,0]],1000000]"]
+        {
+            assert_eq!(number(s).to_string(), s);
+        }
+    }
+}
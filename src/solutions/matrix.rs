@@ -0,0 +1,98 @@
+//Square matrices of counts, optionally reduced by a modulus. Without a modulus every
+//operation is checked and returns None if a value would not fit in a u128.
+//A modulus of zero is rejected with None as well.
+pub type Matrix = Vec<Vec<u128>>;
+
+pub fn pow(mut base : Matrix, mut exp : u64, modulus : Option<u128>) -> Option<Matrix>
+{
+    if modulus == Some(0) { return None; }
+
+    let size = base.len();
+    let mut result : Matrix = (0..size)
+        .map(|i| (0..size).map(|j| u128::from(i == j) % modulus.unwrap_or(u128::MAX)).collect())
+        .collect();
+
+    //Squaring past the last needed bit could overflow needlessly, so stop as soon as exp runs out
+    while exp > 0
+    {
+        if exp & 1 == 1
+        {
+            result = multiply(&result, &base, modulus)?;
+        }
+        exp >>= 1;
+        if exp > 0
+        {
+            base = multiply(&base, &base, modulus)?;
+        }
+    }
+    Some(result)
+}
+
+pub fn multiply(a : &Matrix, b : &Matrix, modulus : Option<u128>) -> Option<Matrix>
+{
+    let size = a.len();
+    let mut result = vec![vec![0u128; size]; size];
+    for i in 0..size
+    {
+        for k in 0..size
+        {
+            if a[i][k] == 0 { continue; }
+            for j in 0..size
+            {
+                result[i][j] = add(result[i][j], mul(a[i][k], b[k][j], modulus)?, modulus)?;
+            }
+        }
+    }
+    Some(result)
+}
+
+pub fn add(a : u128, b : u128, modulus : Option<u128>) -> Option<u128>
+{
+    match modulus
+    {
+        None => a.checked_add(b),
+        Some(0) => None,
+        Some(m) =>
+        {
+            let (a, b) = (a % m, b % m);
+            match a >= m - b
+            {
+                true => Some(a - (m - b)),
+                false => Some(a + b)
+            }
+        }
+    }
+}
+
+pub fn mul(a : u128, b : u128, modulus : Option<u128>) -> Option<u128>
+{
+    match modulus
+    {
+        None => a.checked_mul(b),
+        Some(0) => None,
+        Some(m) => match a.checked_mul(b)
+        {
+            Some(product) => Some(product % m),
+            None =>
+            {
+                //Double-and-add so intermediate values never exceed the modulus
+                let (mut a, mut b, mut product) = (a % m, b % m, 0u128);
+                while b > 0
+                {
+                    if b & 1 == 1 { product = add(product, a, modulus)?; }
+                    a = add(a, a, modulus)?;
+                    b >>= 1;
+                }
+                Some(product)
+            }
+        }
+    }
+}
+
+//The vector m * v
+pub fn apply(m : &Matrix, v : &[u128], modulus : Option<u128>) -> Option<Vec<u128>>
+{
+    m.iter()
+        .map(|row| row.iter().zip(v.iter()).try_fold(0u128, |sum, (&factor, &count)| add(sum, mul(factor, count, modulus)?, modulus)))
+        .collect()
+}
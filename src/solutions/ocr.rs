@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use itertools::Itertools;
+
+//The capital letters Advent of Code draws on grids, six rows high
+const SMALL_FONT : [(char, &str); 18] =
+[
+    ('A', ".##.\n#..#\n#..#\n####\n#..#\n#..#"),
+    ('B', "###.\n#..#\n###.\n#..#\n#..#\n###."),
+    ('C', ".##.\n#..#\n#...\n#...\n#..#\n.##."),
+    ('E', "####\n#...\n###.\n#...\n#...\n####"),
+    ('F', "####\n#...\n###.\n#...\n#...\n#..."),
+    ('G', ".##.\n#..#\n#...\n#.##\n#..#\n.###"),
+    ('H', "#..#\n#..#\n####\n#..#\n#..#\n#..#"),
+    ('I', ".###\n..#.\n..#.\n..#.\n..#.\n.###"),
+    ('J', "..##\n...#\n...#\n...#\n#..#\n.##."),
+    ('K', "#..#\n#.#.\n##..\n#.#.\n#.#.\n#..#"),
+    ('L', "#...\n#...\n#...\n#...\n#...\n####"),
+    ('O', ".##.\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('P', "###.\n#..#\n#..#\n###.\n#...\n#..."),
+    ('R', "###.\n#..#\n#..#\n###.\n#.#.\n#..#"),
+    ('S', ".###\n#...\n#...\n.##.\n...#\n###."),
+    ('U', "#..#\n#..#\n#..#\n#..#\n#..#\n.##."),
+    ('Y', "#...#\n#...#\n.#.#.\n..#..\n..#..\n..#.."),
+    ('Z', "####\n...#\n..#.\n.#..\n#...\n####"),
+];
+
+//The larger ten-row letters
+const LARGE_FONT : [(char, &str); 14] =
+[
+    ('A', "..##..\n.#..#.\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#"),
+    ('B', "#####.\n#....#\n#....#\n#....#\n#####.\n#....#\n#....#\n#....#\n#....#\n#####."),
+    ('C', ".####.\n#....#\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#....#\n.####."),
+    ('E', "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n######"),
+    ('F', "######\n#.....\n#.....\n#.....\n#####.\n#.....\n#.....\n#.....\n#.....\n#....."),
+    ('G', ".####.\n#....#\n#.....\n#.....\n#.....\n#..###\n#....#\n#....#\n#...##\n.###.#"),
+    ('H', "#....#\n#....#\n#....#\n#....#\n######\n#....#\n#....#\n#....#\n#....#\n#....#"),
+    ('J', "...###\n....#.\n....#.\n....#.\n....#.\n....#.\n....#.\n#...#.\n#...#.\n.###.."),
+    ('K', "#....#\n#...#.\n#..#..\n#.#...\n##....\n##....\n#.#...\n#..#..\n#...#.\n#....#"),
+    ('L', "#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n#.....\n######"),
+    ('N', "#....#\n##...#\n##...#\n#.#..#\n#.#..#\n#..#.#\n#..#.#\n#...##\n#...##\n#....#"),
+    ('P', "#####.\n#....#\n#....#\n#....#\n#####.\n#.....\n#.....\n#.....\n#.....\n#....."),
+    ('R', "#####.\n#....#\n#....#\n#....#\n#####.\n#..#..\n#...#.\n#...#.\n#....#\n#....#"),
+    ('Z', "######\n.....#\n.....#\n....#.\n...#..\n..#...\n.#....\n#.....\n#.....\n######"),
+];
+
+type Glyph = HashSet<(i64, i64)>;
+
+//Reads the letters drawn by a set of lit (x, y) points, or None if the drawing
+//is not one line of letters in a known font or contains an unknown glyph
+pub fn recognize(points: impl IntoIterator<Item = (i64, i64)>) -> Option<String>
+{
+    let points = normalize(points.into_iter().collect());
+    let height = points.iter().map(|p| p.1).max()? + 1;
+    let font = match height
+    {
+        6 => &SMALL_FONT[..],
+        10 => &LARGE_FONT[..],
+        _ => return None
+    };
+    let glyphs : Vec<(char, Glyph)> = font.iter()
+        .map(|&(c, art)| (c, normalize(parse_art(art))))
+        .collect();
+
+    split_glyphs(&points).into_iter()
+        .map(|glyph| glyphs.iter().find(|(_, g)| *g == glyph).map(|&(c, _)| c))
+        .collect()
+}
+
+//The recognized text, falling back to the drawing itself
+pub fn read_or_art(points: impl IntoIterator<Item = (i64, i64)>) -> String
+{
+    let points : Vec<_> = points.into_iter().collect();
+    recognize(points.iter().copied()).unwrap_or_else(|| render_art(points))
+}
+
+pub fn render_art(points: impl IntoIterator<Item = (i64, i64)>) -> String
+{
+    let points = normalize(points.into_iter().collect());
+    let width = points.iter().map(|p| p.0).max().map_or(0, |x| x + 1);
+    let height = points.iter().map(|p| p.1).max().map_or(0, |y| y + 1);
+    (0..height).map(|y|
+    {
+        (0..width).map(|x| match points.contains(&(x, y))
+        {
+            true => '#',
+            false => '.'
+        }).collect::<String>()
+    }).join("\n")
+}
+
+fn parse_art(art: &str) -> Glyph
+{
+    art.lines().enumerate()
+        .flat_map(|(y, line)| line.chars().enumerate().filter(|&(_, c)| c == '#').map(move |(x, _)| (x as i64, y as i64)))
+        .collect()
+}
+
+//Shifts points so the topmost and leftmost lit cells sit at 0
+fn normalize(points: Glyph) -> Glyph
+{
+    let min_x = points.iter().map(|p| p.0).min().unwrap_or(0);
+    let min_y = points.iter().map(|p| p.1).min().unwrap_or(0);
+    points.into_iter().map(|(x, y)| (x - min_x, y - min_y)).collect()
+}
+
+//Letters are separated by at least one blank column
+fn split_glyphs(points: &Glyph) -> Vec<Glyph>
+{
+    let columns : Vec<i64> = points.iter().map(|p| p.0).sorted().dedup().collect();
+    let mut glyphs : Vec<Glyph> = Vec::new();
+    let mut prev = None;
+    for &x in columns.iter()
+    {
+        if prev.is_none_or(|p| x > p + 1)
+        {
+            glyphs.push(Glyph::new());
+        }
+        let glyph = glyphs.last_mut().unwrap();
+        glyph.extend(points.iter().filter(|p| p.0 == x).map(|&(x, y)| (x, y)));
+        prev = Some(x);
+    }
+
+    //Keep the vertical offset so letters with blank top rows still line up
+    glyphs.into_iter()
+        .map(|glyph|
+        {
+            let min_x = glyph.iter().map(|p| p.0).min().unwrap();
+            glyph.into_iter().map(|(x, y)| (x - min_x, y)).collect()
+        })
+        .collect()
+}
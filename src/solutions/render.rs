@@ -0,0 +1,82 @@
+use std::{fs, fs::File, io::prelude::*, path::Path};
+
+pub type Colour = (u8, u8, u8);
+
+pub const BLACK : Colour = (0, 0, 0);
+pub const WHITE : Colour = (255, 255, 255);
+
+pub struct Image
+{
+    pub width : usize,
+    pub height : usize,
+    pixels : Vec<Colour>,
+}
+
+impl Image
+{
+    pub fn new(width : usize, height : usize) -> Self
+    {
+        Image { width, height, pixels: vec![BLACK; width * height] }
+    }
+
+    pub fn get(&self, x : usize, y : usize) -> Colour
+    {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x : usize, y : usize, colour : Colour)
+    {
+        if x < self.width && y < self.height
+        {
+            self.pixels[y * self.width + x] = colour;
+        }
+    }
+
+    pub fn to_ppm(&self) -> Vec<u8>
+    {
+        let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend(self.pixels.iter().flat_map(|&(r, g, b)| [r, g, b]));
+        data
+    }
+
+    pub fn to_pgm(&self) -> Vec<u8>
+    {
+        let mut data = format!("P5\n{} {}\n255\n", self.width, self.height).into_bytes();
+        data.extend(self.pixels.iter().map(|&colour| luminance(colour)));
+        data
+    }
+
+    //Writes a PGM or PPM depending on the file extension
+    pub fn save(&self, filename : &str) -> std::io::Result<()>
+    {
+        let path = Path::new(filename);
+        if let Some(dir) = path.parent()
+        {
+            fs::create_dir_all(dir)?;
+        }
+
+        let data = match path.extension().and_then(|ext| ext.to_str())
+        {
+            Some("pgm") => self.to_pgm(),
+            _ => self.to_ppm()
+        };
+        File::create(path)?.write_all(&data)
+    }
+}
+
+fn luminance((r, g, b) : Colour) -> u8
+{
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+//Black -> red -> yellow -> white as value goes from 0 to 1
+pub fn heat(value : f64) -> Colour
+{
+    let v = (value.clamp(0.0, 1.0) * 765.0) as u32;
+    match v
+    {
+        0..=255 => (v as u8, 0, 0),
+        256..=510 => (255, (v - 255) as u8, 0),
+        _ => (255, 255, (v - 510) as u8)
+    }
+}